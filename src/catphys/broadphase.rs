@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use crate::vec2::{Real, Vec2};
+
+// Axis aligned bounding box.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    // Smallest box around all the points. Empty input gives a zero box at the origin.
+    pub fn from_points(points: &[Vec2]) -> Self {
+        let Some(first) = points.first() else {
+            return Self::default();
+        };
+        points
+            .iter()
+            .skip(1)
+            .fold(Self::new(*first, *first), |aabb, p| Self {
+                min: Vec2::new(aabb.min.x.min(p.x), aabb.min.y.min(p.y)),
+                max: Vec2::new(aabb.max.x.max(p.x), aabb.max.y.max(p.y)),
+            })
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::from_points(&[self.min, self.max, other.min, other.max])
+    }
+}
+
+// Finds the pairs of boxes that might be touching, so the narrow phase in
+// Collision::detect only has to look at those.
+// https://en.wikipedia.org/wiki/Sweep_and_prune
+#[derive(Default, Copy, Clone, Debug)]
+pub enum Broadphase {
+    // Sorts the boxes along x and only compares the ones whose x ranges overlap.
+    // Good all round, no tuning needed.
+    #[default]
+    SweepAndPrune,
+    // Buckets the boxes into square cells and only compares boxes sharing a cell.
+    // Fastest when the bodies are about cell_size big. Boxes covering more than
+    // MAX_CELLS_PER_BOX cells are compared with everything instead. See
+    // Broadphase::uniform_grid, a cell_size that isn't finite and positive falls back to
+    // SweepAndPrune.
+    UniformGrid {
+        cell_size: Real,
+    },
+}
+
+// More than this and a box isn't bucketed, so a huge or blown up body can't fill the grid.
+const MAX_CELLS_PER_BOX: Real = 64.0;
+
+impl Broadphase {
+    // None unless cell_size is finite and positive.
+    pub fn uniform_grid(cell_size: Real) -> Option<Broadphase> {
+        (cell_size.is_finite() && cell_size > 0.0).then_some(Broadphase::UniformGrid { cell_size })
+    }
+
+    // Indices into aabbs of every overlapping pair, smaller index first, sorted.
+    pub fn pairs(&self, aabbs: &[Aabb]) -> Vec<(usize, usize)> {
+        let mut pairs = match *self {
+            Broadphase::SweepAndPrune => sweep_and_prune(aabbs),
+            Broadphase::UniformGrid { cell_size } if cell_size.is_finite() && cell_size > 0.0 => {
+                uniform_grid(aabbs, cell_size)
+            }
+            Broadphase::UniformGrid { .. } => sweep_and_prune(aabbs),
+        };
+        // Same order regardless of method, the solver results depend on it.
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+// Boxes sorted along x like in SweepAndPrune, for looking up the ones overlapping an
+// area without checking every box.
+#[derive(Default, Clone, Debug)]
+pub struct SortedAabbs {
+    aabbs: Vec<Aabb>,
+    order: Vec<usize>, // Indices into aabbs by min.x.
+}
+
+impl SortedAabbs {
+    pub fn new(aabbs: Vec<Aabb>) -> Self {
+        let mut order: Vec<usize> = (0..aabbs.len()).collect();
+        order.sort_unstable_by(|&a, &b| aabbs[a].min.x.total_cmp(&aabbs[b].min.x));
+        Self { aabbs, order }
+    }
+
+    // Indices of the boxes overlapping the area, in index order.
+    pub fn overlapping(&self, area: &Aabb) -> Vec<usize> {
+        // Boxes starting right of the area can't overlap it.
+        let end = self
+            .order
+            .partition_point(|&i| self.aabbs[i].min.x.total_cmp(&area.max.x).is_le());
+        let mut found: Vec<usize> = self.order[..end]
+            .iter()
+            .copied()
+            .filter(|&i| self.aabbs[i].overlaps(area))
+            .collect();
+        found.sort_unstable();
+        found
+    }
+}
+
+fn sweep_and_prune(aabbs: &[Aabb]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..aabbs.len()).collect();
+    order.sort_unstable_by(|&a, &b| aabbs[a].min.x.total_cmp(&aabbs[b].min.x));
+
+    let mut pairs = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        // Anything ending before this box starts can't touch it or any later box.
+        active.retain(|&j| aabbs[j].max.x >= aabbs[i].min.x);
+        for &j in &active {
+            if aabbs[i].overlaps(&aabbs[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+        active.push(i);
+    }
+    pairs
+}
+
+fn uniform_grid(aabbs: &[Aabb], cell_size: Real) -> Vec<(usize, usize)> {
+    let cell = |v: Real| (v / cell_size).floor();
+
+    let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    let mut oversized = Vec::new();
+    for (i, aabb) in aabbs.iter().enumerate() {
+        // Counted before converting, huge or infinite boxes would saturate the i32s.
+        let (min, max) = (
+            Vec2::new(cell(aabb.min.x), cell(aabb.min.y)),
+            Vec2::new(cell(aabb.max.x), cell(aabb.max.y)),
+        );
+        let count = (max.x - min.x + 1.0) * (max.y - min.y + 1.0);
+        if count.is_nan() || count > MAX_CELLS_PER_BOX {
+            oversized.push(i);
+            continue;
+        }
+        let cell = |v: Real| v as i32;
+        for x in cell(min.x)..=cell(max.x) {
+            for y in cell(min.y)..=cell(max.y) {
+                cells.entry((x, y)).or_default().push(i);
+            }
+        }
+    }
+
+    // Boxes sharing several cells show up more than once, pairs() removes the duplicates.
+    let mut pairs = Vec::new();
+    for bucket in cells.values() {
+        for (n, &i) in bucket.iter().enumerate() {
+            for &j in &bucket[n + 1..] {
+                if aabbs[i].overlaps(&aabbs[j]) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+    }
+    for &i in &oversized {
+        for j in (0..aabbs.len()).filter(|&j| j != i) {
+            if aabbs[i].overlaps(&aabbs[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(aabbs: &[Aabb]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..aabbs.len() {
+            for j in i + 1..aabbs.len() {
+                if aabbs[i].overlaps(&aabbs[j]) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn scattered_boxes() -> Vec<Aabb> {
+        // Deterministic scatter of differently sized boxes, some overlapping.
+        (0..200)
+            .map(|i| {
+                let x = ((i * 37) % 101) as Real * 0.5 - 25.0;
+                let y = ((i * 53) % 97) as Real * 0.5 - 25.0;
+                let size = 0.5 + (i % 7) as Real * 0.4;
+                Aabb::new(Vec2::new(x, y), Vec2::new(x + size, y + size))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sweep_and_prune_matches_brute_force() {
+        let aabbs = scattered_boxes();
+        let expected = brute_force(&aabbs);
+        assert!(!expected.is_empty());
+        assert_eq!(Broadphase::SweepAndPrune.pairs(&aabbs), expected);
+    }
+
+    #[test]
+    fn uniform_grid_matches_brute_force() {
+        let aabbs = scattered_boxes();
+        let expected = brute_force(&aabbs);
+        for cell_size in [0.5, 2.0, 100.0] {
+            assert_eq!(
+                Broadphase::UniformGrid { cell_size }.pairs(&aabbs),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_grid_handles_huge_boxes() {
+        let mut aabbs = scattered_boxes();
+        aabbs.push(Aabb::new(Vec2::new(-1.0e30, 0.0), Vec2::new(1.0e30, 1.0)));
+        aabbs.push(Aabb::new(
+            Vec2::new(Real::NEG_INFINITY, Real::NEG_INFINITY),
+            Vec2::new(Real::INFINITY, -20.0),
+        ));
+        aabbs.push(Aabb::new(Vec2::new(0.0, 0.0), Vec2::new(Real::NAN, 1.0)));
+        let expected = brute_force(&aabbs);
+        assert_eq!(
+            Broadphase::UniformGrid { cell_size: 1.0 }.pairs(&aabbs),
+            expected
+        );
+
+        // Bad cell sizes are turned down, or fall back to sweep and prune.
+        for cell_size in [0.0, -1.0, Real::NAN, Real::INFINITY] {
+            assert!(Broadphase::uniform_grid(cell_size).is_none());
+            assert_eq!(
+                Broadphase::UniformGrid { cell_size }.pairs(&aabbs),
+                expected
+            );
+        }
+        assert!(Broadphase::uniform_grid(2.0).is_some());
+    }
+
+    #[test]
+    fn sorted_aabbs_match_brute_force() {
+        let aabbs = scattered_boxes();
+        let sorted = SortedAabbs::new(aabbs.clone());
+        for area in aabbs.iter().step_by(13) {
+            let expected: Vec<usize> = (0..aabbs.len())
+                .filter(|&i| aabbs[i].overlaps(area))
+                .collect();
+            assert_eq!(sorted.overlapping(area), expected);
+        }
+        let nowhere = Aabb::new(Vec2::new(100.0, 100.0), Vec2::new(101.0, 101.0));
+        assert!(sorted.overlapping(&nowhere).is_empty());
+    }
+
+    #[test]
+    fn aabb_from_points() {
+        let aabb = Aabb::from_points(&[
+            Vec2::new(1.0, -2.0),
+            Vec2::new(-3.0, 4.0),
+            Vec2::new(0.0, 0.0),
+        ]);
+        assert_eq!(aabb, Aabb::new(Vec2::new(-3.0, -2.0), Vec2::new(1.0, 4.0)));
+        assert!(aabb.contains(Vec2::new(0.5, 3.0)));
+        assert!(!aabb.contains(Vec2::new(2.0, 3.0)));
+    }
+}
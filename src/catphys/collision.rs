@@ -1,143 +1,812 @@
+use crate::manifold::Manifold;
 use crate::physics::Physics;
-use crate::shape::Shape;
-use crate::vec2::Vec2;
+use crate::shape::{closest_on_segment, Shape};
+use crate::vec2::{Real, Vec2};
 
 pub struct Collision {
-    normal: Vec2,
-    depth: f32,
+    manifold: Manifold,
 }
 
 impl Collision {
-    // Is there a better way to implement this double match?
+    // The deepest collision, see detect_all for compound shapes.
     pub fn detect(sa: &Shape, sb: &Shape, pa: &Physics, pb: &Physics) -> Option<Collision> {
+        Self::detect_all(sa, sb, pa, pb)
+            .into_iter()
+            .max_by(|a, b| a.depth().total_cmp(&b.depth()))
+    }
+
+    // One collision for every pair of touching parts, more than one only for compound
+    // shapes.
+    pub fn detect_all(sa: &Shape, sb: &Shape, pa: &Physics, pb: &Physics) -> Vec<Collision> {
+        let parts_a = sa.parts_at(pa.position, pa.rotation);
+        let parts_b = sb.parts_at(pb.position, pb.rotation);
+        let mut collisions = Vec::new();
+        for (i, (sa, position_a, rotation_a)) in parts_a.iter().enumerate() {
+            for (j, (sb, position_b, rotation_b)) in parts_b.iter().enumerate() {
+                let collision =
+                    Self::detect_parts(sa, sb, *position_a, *rotation_a, *position_b, *rotation_b);
+                if let Some(mut collision) = collision {
+                    collision.manifold.set_part((i * parts_b.len() + j) as u32);
+                    collisions.push(collision);
+                }
+            }
+        }
+        collisions
+    }
+
+    // Is there a better way to implement this double match?
+    fn detect_parts(
+        sa: &Shape,
+        sb: &Shape,
+        position_a: Vec2,
+        rotation_a: Real,
+        position_b: Vec2,
+        rotation_b: Real,
+    ) -> Option<Collision> {
+        let rounded =
+            |shape: &Shape| matches!(shape, Shape::Capsule { .. } | Shape::Segment { .. });
+        if rounded(sa) || rounded(sb) {
+            let (va, ra) = sa.core_at(position_a, rotation_a);
+            let (vb, rb) = sb.core_at(position_b, rotation_b);
+            return match (va.len(), vb.len()) {
+                (1, _) => Self::segment_circle(&vb, rb, va[0], ra).map(Collision::flipped),
+                (_, 1) => Self::segment_circle(&va, ra, vb[0], rb),
+                _ => Self::rounded_polygons(&va, ra, &vb, rb),
+            };
+        }
+
         match sa {
             Shape::Circle { radius: ra } => match sb {
                 Shape::Circle { radius: rb } => {
-                    let mut distance = pb.position - pa.position;
+                    let mut distance = position_b - position_a;
                     if distance.magnitude2() < (ra + rb) * (ra + rb) {
                         distance = distance.unit();
-                        let start = pb.position - (distance * *rb);
-                        let end = pa.position + (distance * *ra);
-                        Some(Collision {
-                            normal: distance,
-                            depth: (start - end).magnitude(),
-                        })
+                        let start = position_b - (distance * *rb);
+                        let end = position_a + (distance * *ra);
+                        let mut manifold = Manifold::new(distance);
+                        manifold.add_point((start + end) * 0.5, (start - end).magnitude(), 0);
+                        Some(Collision { manifold })
                     } else {
                         None
                     }
                 }
-                Shape::Rect {
-                    w: width,
-                    h: height,
-                } => Self::rect_circle(*width, *height, *ra, pb, pa),
-                _ => None,
-            },
-            Shape::Rect {
-                w: width,
-                h: height,
-            } => match sb {
-                Shape::Circle { radius: r } => Self::rect_circle(*width, *height, *r, pb, pa),
-                _ => None,
+                Shape::Rect { .. } | Shape::Polygon { .. } => {
+                    Self::polygon_circle(&sb.vertices_at(position_b, rotation_b), position_a, *ra)
+                        .map(Collision::flipped)
+                }
+                _ => None, // Compounds are split up by parts_at.
             },
+            Shape::Rect { .. } | Shape::Polygon { .. } => {
+                let va = || sa.vertices_at(position_a, rotation_a);
+                let vb = || sb.vertices_at(position_b, rotation_b);
+                match sb {
+                    Shape::Circle { radius: r } => Self::polygon_circle(&va(), position_b, *r),
+                    Shape::Rect { w: wb, h: hb } => match sa {
+                        Shape::Rect { w: wa, h: ha } if rotation_a == 0.0 && rotation_b == 0.0 => {
+                            Self::aabb_aabb(*wa, *ha, *wb, *hb, position_a, position_b)
+                        }
+                        _ => Self::polygon_polygon(&va(), &vb()),
+                    },
+                    Shape::Polygon { .. } => Self::polygon_polygon(&va(), &vb()),
+                    _ => None,
+                }
+            }
             _ => None,
         }
     }
 
-    pub fn resolve_penetration(&self, inverse_mass_a: f32, inverse_mass_b: f32) -> (Vec2, Vec2) {
-        let d = self.depth / (inverse_mass_a + inverse_mass_b);
+    pub fn manifold(&self) -> &Manifold {
+        &self.manifold
+    }
+
+    pub fn normal(&self) -> Vec2 {
+        self.manifold.normal()
+    }
+
+    pub fn depth(&self) -> Real {
+        self.manifold.depth()
+    }
+
+    // Static and kinematic bodies have zero inverse masses and aren't moved, nothing is
+    // moved when neither body can be.
+    pub fn resolve_penetration(&self, inverse_mass_a: Real, inverse_mass_b: Real) -> (Vec2, Vec2) {
+        let inverse_mass_sum = inverse_mass_a + inverse_mass_b;
+        if inverse_mass_sum <= 0.0 {
+            return (Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
+        }
+        let d = self.depth() / inverse_mass_sum;
         (
-            self.normal * d * inverse_mass_a,
-            self.normal * d * inverse_mass_b,
+            self.normal() * d * inverse_mass_a,
+            self.normal() * d * inverse_mass_b,
         )
     }
 
-    pub fn resolve_impulse(&self, pa: &Physics, pb: &Physics) -> Vec2 {
-        let e = f32::min(pa.restitution, pb.restitution); // Collision ellasticity
-        let v = pa.velocity - pb.velocity; // Relative velocity
-        let impulse_magnitude =
-            -(1.0 + e) * self.normal.dot(v) / (pa.inverse_mass + pb.inverse_mass);
+    // Applies the impulse at each contact point, so off center hits also spin the bodies.
+    // The r x n terms account for the part of the impulse that goes into rotation, and
+    // a tangential impulse is limited by the Coulomb friction cone.
+    pub fn resolve_impulse(&self, pa: &mut Physics, pb: &mut Physics) {
+        let e = Real::min(pa.restitution, pb.restitution); // Collision ellasticity
+        let static_friction = Real::sqrt(pa.static_friction * pb.static_friction);
+        let dynamic_friction = Real::sqrt(pa.dynamic_friction * pb.dynamic_friction);
+        let normal = self.normal();
+
+        for point in self.manifold.points() {
+            let ra = point.position - pa.position;
+            let rb = point.position - pb.position;
+
+            // Relative velocity
+            let v = pa.velocity_at(point.position) - pb.velocity_at(point.position);
+            let normal_velocity = normal.dot(v);
+            if normal_velocity <= 0.0 {
+                continue; // Already separating.
+            }
+
+            let mass = Self::effective_mass(pa, pb, ra, rb, normal);
+            if mass <= 0.0 {
+                return; // Neither body can be moved.
+            }
+            let impulse_magnitude = -(1.0 + e) * normal_velocity / mass;
+            let impulse = normal * impulse_magnitude;
+            pa.apply_impulse_at(impulse, point.position);
+            pb.apply_impulse_at(-impulse, point.position);
+
+            let v = pa.velocity_at(point.position) - pb.velocity_at(point.position);
+            let tangent = (v - normal * normal.dot(v)).unit();
+            if tangent.nearly_zero() {
+                continue;
+            }
+
+            let mut friction_magnitude =
+                -tangent.dot(v) / Self::effective_mass(pa, pb, ra, rb, tangent);
+            if friction_magnitude.abs() > impulse_magnitude.abs() * static_friction {
+                friction_magnitude =
+                    impulse_magnitude.abs() * dynamic_friction * friction_magnitude.signum();
+            }
+
+            let friction = tangent * friction_magnitude;
+            pa.apply_impulse_at(friction, point.position);
+            pb.apply_impulse_at(-friction, point.position);
+        }
+    }
+
+    // Inverse of how much the relative velocity along direction changes per unit impulse
+    // applied at the contact point with arms ra and rb.
+    fn effective_mass(pa: &Physics, pb: &Physics, ra: Vec2, rb: Vec2, direction: Vec2) -> Real {
+        let ra_cross = ra.cross(direction);
+        let rb_cross = rb.cross(direction);
+        pa.inverse_mass
+            + pb.inverse_mass
+            + ra_cross * ra_cross * pa.inverse_angular_mass
+            + rb_cross * rb_cross * pb.inverse_angular_mass
+    }
 
-        self.normal * impulse_magnitude
+    fn flipped(mut self) -> Collision {
+        self.manifold.flip();
+        self
     }
 
-    //TODO: This is a hacky function. The issue comes from the coordinate system with the
-    //origin in the upper left corner of the screen/window. This makes working with vectors,
-    //specifically finding the right normal vector, stupid. I should switch to world origin in the
-    //center of the screen later when I convert rendering to OpneGL or another GPU accelerated API.
-    fn rect_circle(
-        width: f32,
-        height: f32,
-        radius: f32,
-        pr: &Physics,
-        pc: &Physics,
-    ) -> Option<Collision> {
-        let vert = [
-            Vec2::new(width * -0.5, height * -0.5) + pr.position,
-            Vec2::new(width * 0.5, height * -0.5) + pr.position,
-            Vec2::new(width * 0.5, height * 0.5) + pr.position,
-            Vec2::new(width * -0.5, height * 0.5) + pr.position,
-        ];
+    // Axis aligned boxes don't need the full separating axis test, the overlap on each
+    // axis is already the penetration along that axis.
+    fn aabb_aabb(wa: Real, ha: Real, wb: Real, hb: Real, ca: Vec2, cb: Vec2) -> Option<Collision> {
+        let min_a = ca - Vec2::new(wa, ha) * 0.5;
+        let max_a = ca + Vec2::new(wa, ha) * 0.5;
+        let min_b = cb - Vec2::new(wb, hb) * 0.5;
+        let max_b = cb + Vec2::new(wb, hb) * 0.5;
+
+        let min = Vec2::new(Real::max(min_a.x, min_b.x), Real::max(min_a.y, min_b.y));
+        let max = Vec2::new(Real::min(max_a.x, max_b.x), Real::min(max_a.y, max_b.y));
+        let overlap = max - min;
+        if overlap.x < 0.0 || overlap.y < 0.0 {
+            return None;
+        }
 
-        let mut outside = false;
-        let mut distance_to_edge = f32::MIN; // From circle position.
-        let mut v0 = Vec2::default();
-        let mut v1 = Vec2::default();
+        let center = (min + max) * 0.5;
+        let (normal, depth, points, axis) = if overlap.x < overlap.y {
+            let sign = if cb.x < ca.x { -1.0 } else { 1.0 };
+            (
+                Vec2::new(sign, 0.0),
+                overlap.x,
+                [Vec2::new(center.x, min.y), Vec2::new(center.x, max.y)],
+                0,
+            )
+        } else {
+            let sign = if cb.y < ca.y { -1.0 } else { 1.0 };
+            (
+                Vec2::new(0.0, sign),
+                overlap.y,
+                [Vec2::new(min.x, center.y), Vec2::new(max.x, center.y)],
+                2,
+            )
+        };
 
-        for p0 in 0..4 {
-            let p1 = (p0 + 1) % 4; //TODO: Avoid this division.
+        let mut manifold = Manifold::new(normal);
+        manifold.add_point(points[0], depth, axis);
+        manifold.add_point(points[1], depth, axis + 1);
+        Some(Collision { manifold })
+    }
 
-            v0 = vert[p0];
-            v1 = vert[p1];
+    // Largest distance from an edge of polygon a to the deepest vertex of polygon b,
+    // together with the index of that edge. Positive means a separating axis was found.
+    pub(crate) fn max_separation(va: &[Vec2], vb: &[Vec2]) -> (Real, usize) {
+        let mut best_separation = Real::MIN;
+        let mut best_edge = 0;
 
+        for i in 0..va.len() {
+            let v0 = va[i];
+            let v1 = va[(i + 1) % va.len()];
             let normal = (v1 - v0).normal_positive().unit();
-            let projection = (pc.position - v0).dot(normal);
 
-            if projection > 0.0 {
-                distance_to_edge = projection;
-                outside = true;
-                break;
+            let separation = vb
+                .iter()
+                .map(|v| (*v - v0).dot(normal))
+                .fold(Real::MAX, Real::min);
+
+            if separation > best_separation {
+                best_separation = separation;
+                best_edge = i;
             }
+        }
+
+        (best_separation, best_edge)
+    }
 
-            if projection > distance_to_edge {
-                distance_to_edge = projection;
+    // Keep the part of segment p that lies behind the plane normal.dot(x) = offset.
+    fn clip(p: [Vec2; 2], normal: Vec2, offset: Real) -> Option<[Vec2; 2]> {
+        let d0 = normal.dot(p[0]) - offset;
+        let d1 = normal.dot(p[1]) - offset;
+
+        if d0 > 0.0 && d1 > 0.0 {
+            return None;
+        }
+
+        let mut out = p;
+        if d0 * d1 < 0.0 {
+            let intersection = p[0] + (p[1] - p[0]) * (d0 / (d0 - d1));
+            if d0 > 0.0 {
+                out[0] = intersection;
+            } else {
+                out[1] = intersection;
             }
         }
 
-        if outside {
-            let mut edge = v1 - v0;
-            let mut intersect = pc.position - v0;
-            if intersect.dot(edge) < 0.0 {
-                if intersect.magnitude2() <= radius * radius {
-                    return Some(Collision {
-                        normal: -intersect.unit(),
-                        depth: radius - intersect.magnitude(),
-                    });
+        Some(out)
+    }
+
+    // https://en.wikipedia.org/wiki/Hyperplane_separation_theorem
+    // Separating axis test over the edge normals of both polygons, then the incident
+    // edge is clipped against the reference edge to find up to two contact points.
+    fn polygon_polygon(va: &[Vec2], vb: &[Vec2]) -> Option<Collision> {
+        let (separation_a, edge_a) = Self::max_separation(va, vb);
+        if separation_a > 0.0 {
+            return None;
+        }
+
+        let (separation_b, edge_b) = Self::max_separation(vb, va);
+        if separation_b > 0.0 {
+            return None;
+        }
+
+        // Prefer a as the reference polygon so the normal doesn't flip between frames
+        // when both axes are almost equally good.
+        let flip = separation_b > 0.98 * separation_a + 0.001;
+        let (reference, incident, edge) = if flip {
+            (vb, va, edge_b)
+        } else {
+            (va, vb, edge_a)
+        };
+
+        let r0 = reference[edge];
+        let r1 = reference[(edge + 1) % reference.len()];
+        let tangent = (r1 - r0).unit();
+        let normal = tangent.normal_positive();
+
+        // Incident edge is the one most facing against the reference normal.
+        let mut incident_edge = 0;
+        let mut min_dot = Real::MAX;
+        for i in 0..incident.len() {
+            let v0 = incident[i];
+            let v1 = incident[(i + 1) % incident.len()];
+            let dot = (v1 - v0).normal_positive().unit().dot(normal);
+            if dot < min_dot {
+                min_dot = dot;
+                incident_edge = i;
+            }
+        }
+
+        let segment = [
+            incident[incident_edge],
+            incident[(incident_edge + 1) % incident.len()],
+        ];
+        let segment = Self::clip(segment, -tangent, -tangent.dot(r0))?;
+        let segment = Self::clip(segment, tangent, tangent.dot(r1))?;
+
+        let mut manifold = Manifold::new(if flip { -normal } else { normal });
+        for (i, p) in segment.into_iter().enumerate() {
+            let separation = (p - r0).dot(normal);
+            if separation <= 0.0 {
+                // Identifies the pair of features, so the point can be matched next frame.
+                let id = (flip as u32) << 31 | (edge as u32) << 16 | (incident_edge + i) as u32;
+                manifold.add_point(p - normal * (separation * 0.5), -separation, id);
+            }
+        }
+
+        if manifold.points().is_empty() {
+            None
+        } else {
+            Some(Collision { manifold })
+        }
+    }
+
+    // Capsule or segment core against a circle, normal points from the segment towards
+    // the circle.
+    fn segment_circle(segment: &[Vec2], ra: Real, center: Vec2, rb: Real) -> Option<Collision> {
+        let closest = closest_on_segment(segment[0], segment[1], center);
+        let offset = center - closest;
+        let radius = ra + rb;
+        if offset.magnitude2() > radius * radius {
+            return None;
+        }
+        let normal = if offset.nearly_zero() {
+            (segment[1] - segment[0]).normal_positive().unit()
+        } else {
+            offset.unit()
+        };
+
+        let surface_a = closest + normal * ra;
+        let surface_b = center - normal * rb;
+        let mut manifold = Manifold::new(normal);
+        manifold.add_point(
+            (surface_a + surface_b) * 0.5,
+            radius - offset.magnitude(),
+            0,
+        );
+        Some(Collision { manifold })
+    }
+
+    // Convex cores grown by radii, for capsules and segments against rects, polygons and
+    // each other. Like polygon_polygon when an edge faces the other shape, so a capsule
+    // lying on a box gets two points. Otherwise the closest points of the cores give one.
+    fn rounded_polygons(va: &[Vec2], ra: Real, vb: &[Vec2], rb: Real) -> Option<Collision> {
+        let radius = ra + rb;
+        let (separation_a, edge_a) = Self::max_separation(va, vb);
+        let (separation_b, edge_b) = Self::max_separation(vb, va);
+        if separation_a > radius || separation_b > radius {
+            return None;
+        }
+
+        let (p, q, overlapping) = Self::closest_points(va, vb);
+        let distance = (q - p).magnitude();
+        let closest_point = || {
+            let normal = (q - p) / distance;
+            let mut manifold = Manifold::new(normal);
+            let position = (p + normal * ra + q - normal * rb) * 0.5;
+            manifold.add_point(position, radius - distance, 1 << 30);
+            Some(Collision { manifold })
+        };
+        if !overlapping {
+            if distance > radius {
+                return None;
+            }
+            // Corner against corner, no edge lines up with the closest points.
+            if distance - Real::max(separation_a, separation_b) > 0.01 * radius {
+                return closest_point();
+            }
+        }
+
+        let flip = separation_b > 0.98 * separation_a + 0.001;
+        let (reference, incident, edge, reference_radius, incident_radius) = if flip {
+            (vb, va, edge_b, rb, ra)
+        } else {
+            (va, vb, edge_a, ra, rb)
+        };
+
+        let r0 = reference[edge];
+        let r1 = reference[(edge + 1) % reference.len()];
+        let tangent = (r1 - r0).unit();
+        let normal = tangent.normal_positive();
+
+        let mut incident_edge = 0;
+        let mut min_dot = Real::MAX;
+        for i in 0..incident.len() {
+            let v0 = incident[i];
+            let v1 = incident[(i + 1) % incident.len()];
+            let dot = (v1 - v0).normal_positive().unit().dot(normal);
+            if dot < min_dot {
+                min_dot = dot;
+                incident_edge = i;
+            }
+        }
+
+        let segment = [
+            incident[incident_edge],
+            incident[(incident_edge + 1) % incident.len()],
+        ];
+        let clipped = Self::clip(segment, -tangent, -tangent.dot(r0))
+            .and_then(|segment| Self::clip(segment, tangent, tangent.dot(r1)));
+
+        let mut manifold = Manifold::new(if flip { -normal } else { normal });
+        for (i, p) in clipped.into_iter().flatten().enumerate() {
+            let separation = (p - r0).dot(normal);
+            if separation <= radius {
+                // Halfway between the grown surfaces.
+                let position =
+                    p - normal * ((separation - reference_radius + incident_radius) * 0.5);
+                let id = (flip as u32) << 31 | (edge as u32) << 16 | (incident_edge + i) as u32;
+                manifold.add_point(position, radius - separation, id);
+            }
+        }
+
+        if !manifold.points().is_empty() {
+            Some(Collision { manifold })
+        } else if !overlapping {
+            closest_point()
+        } else {
+            None
+        }
+    }
+
+    // Closest points of the outlines of two convex cores, and whether the cores overlap.
+    // Cores with two vertices are segments, with one a point.
+    fn closest_points(va: &[Vec2], vb: &[Vec2]) -> (Vec2, Vec2, bool) {
+        let contains = |verts: &[Vec2], point: Vec2| {
+            verts.len() > 2 && Self::max_separation(verts, &[point]).0 <= 0.0
+        };
+        let mut best = (va[0], vb[0]);
+        let mut best_distance = Real::MAX;
+        for (a0, a1) in edges(va) {
+            for (b0, b1) in edges(vb) {
+                let (p, q) = closest_between_segments(a0, a1, b0, b1);
+                let distance = (q - p).magnitude2();
+                if distance < best_distance {
+                    best = (p, q);
+                    best_distance = distance;
                 }
+            }
+        }
+        let overlapping = best_distance == 0.0 || contains(va, vb[0]) || contains(vb, va[0]);
+        (best.0, best.1, overlapping)
+    }
+
+    // Distance between the surfaces of two cores grown by radii, when they overlap only
+    // a lower bound: minus the penetration along the best edge normal or more.
+    pub(crate) fn rounded_separation(va: &[Vec2], ra: Real, vb: &[Vec2], rb: Real) -> Real {
+        let (p, q, overlapping) = Self::closest_points(va, vb);
+        if !overlapping {
+            return (q - p).magnitude() - ra - rb;
+        }
+        let faces = |v: &[Vec2], w: &[Vec2]| {
+            if v.len() > 1 {
+                Self::max_separation(v, w).0
             } else {
-                edge = v0 - v1;
-                intersect = pc.position - v1;
-                if intersect.dot(edge) < 0.0 {
-                    if intersect.magnitude2() <= radius * radius {
-                        return Some(Collision {
-                            normal: -intersect.unit(),
-                            depth: radius - intersect.magnitude(),
-                        });
-                    }
-                } else if distance_to_edge <= radius {
-                    return Some(Collision {
-                        normal: (v0 - v1).normal_positive().unit(),
-                        depth: radius - distance_to_edge,
-                    });
-                }
+                Real::MIN
+            }
+        };
+        Real::max(faces(va, vb), faces(vb, va)).min(0.0) - ra - rb
+    }
+
+    // Normal points from the polygon towards the circle.
+    fn polygon_circle(verts: &[Vec2], center: Vec2, radius: Real) -> Option<Collision> {
+        let mut separation = Real::MIN;
+        let mut edge = 0;
+        for i in 0..verts.len() {
+            let v0 = verts[i];
+            let v1 = verts[(i + 1) % verts.len()];
+            let s = (center - v0).dot((v1 - v0).normal_positive().unit());
+            if s > radius {
+                return None;
+            }
+            if s > separation {
+                separation = s;
+                edge = i;
+            }
+        }
+
+        let v0 = verts[edge];
+        let v1 = verts[(edge + 1) % verts.len()];
+        let face_normal = (v1 - v0).normal_positive().unit();
+
+        let (normal, depth, surface_point) = if (center - v0).dot(v1 - v0) < 0.0 && separation > 0.0
+        {
+            // Closest to vertex v0.
+            let distance = center - v0;
+            if distance.magnitude2() > radius * radius {
+                return None;
             }
+            (distance.unit(), radius - distance.magnitude(), v0)
+        } else if (center - v1).dot(v0 - v1) < 0.0 && separation > 0.0 {
+            // Closest to vertex v1.
+            let distance = center - v1;
+            if distance.magnitude2() > radius * radius {
+                return None;
+            }
+            (distance.unit(), radius - distance.magnitude(), v1)
         } else {
-            // Inside
-            return Some(Collision {
-                normal: (v0 - v1).normal_positive().unit(),
-                depth: radius - distance_to_edge,
-            });
+            // Closest to the face, or the center is inside the polygon.
+            (
+                face_normal,
+                radius - separation,
+                center - face_normal * separation,
+            )
+        };
+
+        let mut manifold = Manifold::new(normal);
+        manifold.add_point((surface_point + (center - normal * radius)) * 0.5, depth, 0);
+        Some(Collision { manifold })
+    }
+}
+
+// Edges of a convex core, a segment has one and a point one of zero length.
+fn edges(verts: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    let count = if verts.len() > 2 { verts.len() } else { 1 };
+    (0..count).map(move |i| (verts[i], verts[(i + 1) % verts.len()]))
+}
+
+// Closest points of two segments. When they don't cross, one of the points is an end.
+fn closest_between_segments(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> (Vec2, Vec2) {
+    let (da, db) = (a1 - a0, b1 - b0);
+    let side_b0 = da.cross(b0 - a0);
+    let side_b1 = da.cross(b1 - a0);
+    let side_a0 = db.cross(a0 - b0);
+    let side_a1 = db.cross(a1 - b0);
+    if side_b0 * side_b1 < 0.0 && side_a0 * side_a1 < 0.0 {
+        let crossing = a0 + da * (side_a0 / (side_a0 - side_a1));
+        return (crossing, crossing);
+    }
+
+    [
+        (a0, closest_on_segment(b0, b1, a0)),
+        (a1, closest_on_segment(b0, b1, a1)),
+        (closest_on_segment(a0, a1, b0), b0),
+        (closest_on_segment(a0, a1, b1), b1),
+    ]
+    .into_iter()
+    .min_by(|(p0, q0), (p1, q1)| {
+        (*q0 - *p0)
+            .magnitude2()
+            .total_cmp(&(*q1 - *p1).magnitude2())
+    })
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Collision;
+    use crate::physics::Physics;
+    use crate::shape::Shape;
+    use crate::vec2::{consts, Real, Vec2};
+
+    fn square(size: Real) -> Shape {
+        let h = size * 0.5;
+        Shape::Polygon {
+            verts: vec![
+                Vec2::new(-h, -h),
+                Vec2::new(h, -h),
+                Vec2::new(h, h),
+                Vec2::new(-h, h),
+            ],
         }
+    }
+
+    #[test]
+    fn polygon_polygon_overlap() {
+        let pa = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let pb = Physics::new(Vec2::new(1.5, 0.0), 1.0, 1.0, 0.5);
+        let c = Collision::detect(&square(2.0), &square(2.0), &pa, &pb).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(1.0, 0.0)));
+        assert!((c.depth() - 0.5).abs() < 0.001);
+        assert_eq!(c.manifold().points().len(), 2);
+    }
+
+    #[test]
+    fn polygon_polygon_separated() {
+        let pa = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let pb = Physics::new(Vec2::new(2.5, 0.0), 1.0, 1.0, 0.5);
+        assert!(Collision::detect(&square(2.0), &square(2.0), &pa, &pb).is_none());
+    }
+
+    #[test]
+    fn polygon_circle_normal_points_a_to_b() {
+        let circle = Shape::Circle { radius: 1.0 };
+        let pa = Physics::new(Vec2::new(0.0, -1.5), 1.0, 1.0, 0.5);
+        let pb = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let c = Collision::detect(&circle, &square(2.0), &pa, &pb).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!((c.depth() - 0.5).abs() < 0.001);
+
+        let c = Collision::detect(&square(2.0), &circle, &pb, &pa).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, -1.0)));
+    }
+
+    #[test]
+    fn off_center_impulse_spins() {
+        let rect = Shape::Rect { w: 2.0, h: 2.0 };
+        let mut pa = Physics::new(Vec2::new(1.5, -1.9), 1.0, rect.rotational_inertia(), 0.0);
+        let mut pb = Physics::new(Vec2::new(0.0, 0.0), 1.0, rect.rotational_inertia(), 0.0);
+        pa.velocity = Vec2::new(0.0, 1.0);
+        let c = Collision::detect(&rect, &rect, &pa, &pb).unwrap();
+        c.resolve_impulse(&mut pa, &mut pb);
+        assert!(pa.angular_velocity != 0.0);
+        assert!(pb.angular_velocity != 0.0);
+        assert!(pa.velocity.y < 1.0);
+    }
+
+    #[test]
+    fn friction_slows_sliding() {
+        let rect = Shape::Rect { w: 2.0, h: 2.0 };
+        let mut pa = Physics::new(Vec2::new(0.0, -1.95), 1.0, rect.rotational_inertia(), 0.0);
+        let mut ground = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        pa.velocity = Vec2::new(3.0, 1.0);
+        let c = Collision::detect(&rect, &rect, &pa, &ground).unwrap();
+        c.resolve_impulse(&mut pa, &mut ground);
+        assert!(pa.velocity.x < 3.0);
+        assert!(pa.velocity.x > 0.0);
+        assert!(pa.velocity.y < 1.0);
+
+        pa.static_friction = 0.0;
+        pa.dynamic_friction = 0.0;
+        pa.velocity = Vec2::new(3.0, 1.0);
+        pa.angular_velocity = 0.0;
+        c.resolve_impulse(&mut pa, &mut ground);
+        assert!((pa.velocity.x - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn static_bodies_are_not_moved() {
+        let rect = Shape::Rect { w: 2.0, h: 2.0 };
+        let mut pa = Physics::new(Vec2::new(0.0, -1.5), 1.0, rect.rotational_inertia(), 0.0);
+        let mut ground = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        pa.velocity = Vec2::new(0.0, 2.0);
+        let c = Collision::detect(&rect, &rect, &pa, &ground).unwrap();
+
+        let (da, db) = c.resolve_penetration(pa.inverse_mass, ground.inverse_mass);
+        assert!(da.nearly_eq(Vec2::new(0.0, 0.5)));
+        assert!(db.nearly_zero());
+        c.resolve_impulse(&mut pa, &mut ground);
+        assert!(pa.velocity.y < 1.5);
+        assert!(ground.velocity.nearly_zero());
+
+        let mut wall = Physics::new_static(Vec2::new(0.0, -1.5), 0.0);
+        wall.velocity = Vec2::new(0.0, 2.0);
+        let c = Collision::detect(&rect, &rect, &wall, &ground).unwrap();
+        let (da, db) = c.resolve_penetration(wall.inverse_mass, ground.inverse_mass);
+        assert!(da.nearly_zero() && db.nearly_zero());
+        c.resolve_impulse(&mut wall, &mut ground);
+        assert!(ground.velocity.nearly_zero());
+    }
+
+    #[test]
+    fn rect_rect_aligned() {
+        let rect = Shape::Rect { w: 2.0, h: 1.0 };
+        let pa = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let pb = Physics::new(Vec2::new(0.5, 0.8), 1.0, 1.0, 0.5);
+        let c = Collision::detect(&rect, &rect, &pa, &pb).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!((c.depth() - 0.2).abs() < 0.001);
+        assert_eq!(c.manifold().points().len(), 2);
 
-        None
+        let c = Collision::detect(&rect, &rect, &pb, &pa).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, -1.0)));
+
+        let pb = Physics::new(Vec2::new(2.1, 0.0), 1.0, 1.0, 0.5);
+        assert!(Collision::detect(&rect, &rect, &pa, &pb).is_none());
+    }
+
+    #[test]
+    fn rect_rect_oriented() {
+        let rect = Shape::Rect { w: 2.0, h: 2.0 };
+        let pa = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let mut pb = Physics::new(Vec2::new(0.0, 2.3), 1.0, 1.0, 0.5);
+        pb.rotation = consts::FRAC_PI_4;
+        let c = Collision::detect(&rect, &rect, &pa, &pb).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!((c.depth() - (consts::SQRT_2 - 1.3)).abs() < 0.001);
+
+        pb.position.y = 2.5;
+        assert!(Collision::detect(&rect, &rect, &pa, &pb).is_none());
+    }
+
+    #[test]
+    fn polygon_rect_rotated() {
+        let rect = Shape::Rect { w: 2.0, h: 2.0 };
+        let pa = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.5);
+        let mut pb = Physics::new(Vec2::new(2.2, 0.0), 1.0, 1.0, 0.5);
+        pb.rotation = consts::FRAC_PI_4;
+        // The rotated square reaches sqrt(2) towards a, so it overlaps by about 0.21.
+        let c = Collision::detect(&square(2.0), &rect, &pa, &pb).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(1.0, 0.0)));
+        assert!((c.depth() - (consts::SQRT_2 - 1.2)).abs() < 0.001);
+        assert_eq!(c.manifold().points().len(), 1);
+    }
+
+    #[test]
+    fn capsule_collisions() {
+        let capsule = Shape::Capsule {
+            length: 2.0,
+            radius: 0.5,
+        };
+        let lying = |x: Real, y: Real| {
+            let mut p = Physics::new(Vec2::new(x, y), 1.0, 1.0, 0.0);
+            p.rotation = consts::FRAC_PI_2;
+            p
+        };
+        let ground = Physics::new_static(Vec2::new(0.0, 1.0), 0.0);
+        let floor = Shape::Rect { w: 10.0, h: 1.0 };
+
+        // Lying flat on the floor, touching along its whole length.
+        let c = Collision::detect(&capsule, &floor, &lying(0.0, 0.1), &ground).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert_eq!(c.manifold().points().len(), 2);
+        assert!((c.depth() - 0.1).abs() < 0.001);
+        assert!(Collision::detect(&capsule, &floor, &lying(0.0, -0.1), &ground).is_none());
+
+        // Standing on the floor, the round end touches in one point.
+        let standing = Physics::new(Vec2::new(0.0, -0.9), 1.0, 1.0, 0.0);
+        let c = Collision::detect(&floor, &capsule, &ground, &standing).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, -1.0)));
+        assert_eq!(c.manifold().points().len(), 1);
+        assert!((c.depth() - 0.1).abs() < 0.001);
+
+        // End to end with another capsule, on the same line.
+        let above = Physics::new(Vec2::new(0.0, -3.8), 1.0, 1.0, 0.0);
+        let c = Collision::detect(&capsule, &capsule, &above, &standing).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!((c.depth() - 0.1).abs() < 0.001);
+
+        let ball = Shape::Circle { radius: 0.5 };
+        let c = Collision::detect(&ball, &capsule, &lying(0.9, -0.5), &lying(0.0, 0.4)).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!((c.depth() - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn segment_collisions() {
+        let segment = Shape::Segment {
+            a: Vec2::new(-5.0, 0.0),
+            b: Vec2::new(5.0, 0.0),
+        };
+        let origin = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+
+        // Hit from above or below, the normal points away from the segment.
+        let ball = Shape::Circle { radius: 0.5 };
+        let above = Physics::new(Vec2::new(1.0, -0.4), 1.0, 1.0, 0.0);
+        let c = Collision::detect(&segment, &ball, &origin, &above).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, -1.0)));
+        assert!((c.depth() - 0.1).abs() < 0.001);
+        let below = Physics::new(Vec2::new(1.0, 0.4), 1.0, 1.0, 0.0);
+        let c = Collision::detect(&segment, &ball, &origin, &below).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+
+        let c = Collision::detect(&square(1.0), &segment, &above, &origin).unwrap();
+        assert!(c.normal().nearly_eq(Vec2::new(0.0, 1.0)));
+        assert_eq!(c.manifold().points().len(), 2);
+        assert!((c.depth() - 0.1).abs() < 0.001);
+        let past_the_end = Physics::new(Vec2::new(6.0, -0.4), 1.0, 1.0, 0.0);
+        assert!(Collision::detect(&square(1.0), &segment, &past_the_end, &origin).is_none());
+    }
+
+    #[test]
+    fn compound_parts_keep_their_ids() {
+        // Far enough along that the part index needs more than 7 bits.
+        let points: Vec<Vec2> = (0..=300).map(|i| Vec2::new(i as Real, 0.0)).collect();
+        let chain = Shape::chain(&points);
+        let segment = Shape::Segment {
+            a: Vec2::new(200.0, 0.0),
+            b: Vec2::new(201.0, 0.0),
+        };
+        let capsule = Shape::Capsule {
+            length: 0.5,
+            radius: 0.25,
+        };
+        let origin = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        let above = Physics::new(Vec2::new(200.5, -0.45), 1.0, 1.0, 0.0);
+
+        let single = Collision::detect(&capsule, &segment, &above, &origin).unwrap();
+        let parts = Collision::detect_all(&capsule, &chain, &above, &origin);
+        assert_eq!(parts.len(), 1);
+        let (expected, found) = (single.manifold().points(), parts[0].manifold().points());
+        assert_eq!(found.len(), expected.len());
+        for (e, f) in expected.iter().zip(found) {
+            assert_eq!((f.part, f.id), (200, e.id));
+        }
     }
 }
@@ -0,0 +1,60 @@
+// Which bodies collide. A body collides with another when each one's mask has a bit of
+// the other's category set. Bodies in the same non-zero group always collide when the
+// group is positive and never when it's negative, whatever the masks say.
+// https://box2d.org/documentation/md_simulation.html#filtering
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    pub category: u16,
+    pub mask: u16,
+    pub group: i16,
+}
+
+impl Default for Filter {
+    // Collides with everything.
+    fn default() -> Self {
+        Self {
+            category: 1,
+            mask: u16::MAX,
+            group: 0,
+        }
+    }
+}
+
+impl Filter {
+    pub fn should_collide(&self, other: &Filter) -> bool {
+        if self.group != 0 && self.group == other.group {
+            return self.group > 0;
+        }
+        self.mask & other.category != 0 && other.mask & self.category != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_and_groups() {
+        let scenery = Filter::default();
+        let particle = Filter {
+            category: 2,
+            mask: !2,
+            group: 0,
+        };
+        assert!(scenery.should_collide(&particle));
+        assert!(!particle.should_collide(&particle));
+
+        // The group wins over the masks.
+        let friends = Filter {
+            group: 3,
+            ..particle
+        };
+        assert!(friends.should_collide(&friends));
+        let ghosts = Filter {
+            group: -3,
+            ..scenery
+        };
+        assert!(!ghosts.should_collide(&ghosts));
+        assert!(ghosts.should_collide(&scenery));
+    }
+}
@@ -1,17 +1,240 @@
-use crate::vec2::Vec2;
+use crate::physics::Physics;
+use crate::shape::Shape;
+use crate::vec2::{Real, Vec2};
+use crate::world::BodyHandle;
 
 pub struct Force {}
 
 impl Force {
-    pub fn friction(k: f32, velocity: Vec2) -> Vec2 {
+    pub fn friction(k: Real, velocity: Vec2) -> Vec2 {
         -velocity * k
     }
 
-    pub fn drag(k: f32, velocity: Vec2) -> Vec2 {
+    pub fn drag(k: Real, velocity: Vec2) -> Vec2 {
         -velocity * velocity.magnitude2() * k
     }
 
-    pub fn spring(_k: f32, _object_position: Vec2, _anchor_position: Vec2) -> Vec2 {
-        Vec2::new(0.0, 0.0)
+    // Hooke's law, pulls the object towards the anchor when stretched past rest_length
+    // and pushes it away when compressed.
+    pub fn spring(
+        k: Real,
+        rest_length: Real,
+        object_position: Vec2,
+        anchor_position: Vec2,
+    ) -> Vec2 {
+        let d = object_position - anchor_position;
+        -d.unit() * (k * (d.magnitude() - rest_length))
+    }
+
+    // Resists the stretching and compressing of a spring, only along the spring.
+    // relative_velocity is the object's velocity minus the anchor's.
+    pub fn spring_damping(
+        c: Real,
+        object_position: Vec2,
+        anchor_position: Vec2,
+        relative_velocity: Vec2,
+    ) -> Vec2 {
+        let direction = (object_position - anchor_position).unit();
+        -direction * (relative_velocity.dot(direction) * c)
+    }
+}
+
+// Adds forces to a body during every World::step, see ForceRegistry. Verlet and RK4 bodies
+// need the forces at several points in the step, so a generator can be run up to four
+// times per step, with the body moved to each point. It should only depend on the body's
+// state then, anything counting calls or adding impulses would act more than once.
+pub trait ForceGenerator {
+    fn apply(&mut self, physics: &mut Physics, shape: &Shape, dt: Real);
+}
+
+// Any closure works as a generator.
+impl<F> ForceGenerator for F
+where
+    F: FnMut(&mut Physics, &Shape, Real),
+{
+    fn apply(&mut self, physics: &mut Physics, shape: &Shape, dt: Real) {
+        self(physics, shape, dt)
+    }
+}
+
+// Extra gravity, on top of the World's.
+pub struct Gravity {
+    pub acceleration: Vec2,
+}
+
+impl ForceGenerator for Gravity {
+    fn apply(&mut self, physics: &mut Physics, _shape: &Shape, _dt: Real) {
+        if physics.is_dynamic() {
+            physics.apply_force(self.acceleration * physics.mass);
+        }
+    }
+}
+
+// Linear and quadratic air resistance, see Force::friction and Force::drag.
+pub struct Drag {
+    pub k1: Real,
+    pub k2: Real,
+}
+
+impl ForceGenerator for Drag {
+    fn apply(&mut self, physics: &mut Physics, _shape: &Shape, _dt: Real) {
+        let velocity = physics.velocity;
+        physics.apply_force(Force::friction(self.k1, velocity) + Force::drag(self.k2, velocity));
+    }
+}
+
+// Archimedes' principle for a fluid filling everything below surface_y (y grows down).
+// The submerged part of the shape is estimated from its bounding box.
+// https://en.wikipedia.org/wiki/Buoyancy
+pub struct Buoyancy {
+    pub surface_y: Real,
+    pub density: Real,
+    pub gravity: Vec2,
+    pub linear_drag: Real, // Applied to the submerged fraction, so things stop bobbing.
+}
+
+impl ForceGenerator for Buoyancy {
+    fn apply(&mut self, physics: &mut Physics, shape: &Shape, _dt: Real) {
+        let aabb = shape.aabb(physics);
+        let height = aabb.max.y - aabb.min.y;
+        if height <= 0.0 || aabb.max.y <= self.surface_y {
+            return;
+        }
+        let submerged = ((aabb.max.y - self.surface_y) / height).min(1.0);
+
+        let displaced_mass = shape.area() * submerged * self.density;
+        physics.apply_force(-self.gravity * displaced_mass);
+        physics.apply_force(Force::friction(
+            self.linear_drag * submerged,
+            physics.velocity,
+        ));
+    }
+}
+
+// Spring from a body to a fixed point in the world.
+pub struct AnchoredSpring {
+    pub anchor: Vec2,
+    pub k: Real,
+    pub rest_length: Real,
+    pub damping: Real,
+}
+
+impl ForceGenerator for AnchoredSpring {
+    fn apply(&mut self, physics: &mut Physics, _shape: &Shape, _dt: Real) {
+        let position = physics.position;
+        physics.apply_force(
+            Force::spring(self.k, self.rest_length, position, self.anchor)
+                + Force::spring_damping(self.damping, position, self.anchor, physics.velocity),
+        );
+    }
+}
+
+// Spring between two bodies, attached at points given in body space.
+pub struct Spring {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub local_a: Vec2,
+    pub local_b: Vec2,
+    pub k: Real,
+    pub rest_length: Real,
+    pub damping: Real,
+}
+
+impl Spring {
+    // Attached at the body centers, no damping.
+    pub fn new(a: BodyHandle, b: BodyHandle, k: Real, rest_length: Real) -> Self {
+        Self {
+            a,
+            b,
+            local_a: Vec2::new(0.0, 0.0),
+            local_b: Vec2::new(0.0, 0.0),
+            k,
+            rest_length,
+            damping: 0.0,
+        }
+    }
+
+    pub(crate) fn apply(&self, pa: &mut Physics, pb: &mut Physics) {
+        let point_a = pa.transform().apply(self.local_a);
+        let point_b = pb.transform().apply(self.local_b);
+        let relative_velocity = pa.velocity_at(point_a) - pb.velocity_at(point_b);
+
+        let force = Force::spring(self.k, self.rest_length, point_a, point_b)
+            + Force::spring_damping(self.damping, point_a, point_b, relative_velocity);
+        pa.apply_force_at(force, point_a);
+        pb.apply_force_at(-force, point_b);
+    }
+}
+
+// Which bodies a generator acts on.
+pub enum ForceTarget {
+    All,
+    Bodies(Vec<BodyHandle>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ForceHandle(u32);
+
+// Force generators and springs a World applies every step. Handles of removed bodies
+// are skipped.
+#[derive(Default)]
+pub struct ForceRegistry {
+    pub(crate) generators: Vec<(ForceHandle, ForceTarget, Box<dyn ForceGenerator>)>,
+    pub(crate) springs: Vec<(ForceHandle, Spring)>,
+    next_handle: u32,
+}
+
+impl ForceRegistry {
+    pub fn add<G>(&mut self, target: ForceTarget, generator: G) -> ForceHandle
+    where
+        G: ForceGenerator + 'static,
+    {
+        let handle = self.next_handle();
+        self.generators.push((handle, target, Box::new(generator)));
+        handle
+    }
+
+    pub fn add_spring(&mut self, spring: Spring) -> ForceHandle {
+        let handle = self.next_handle();
+        self.springs.push((handle, spring));
+        handle
+    }
+
+    // True if there was something to remove.
+    pub fn remove(&mut self, handle: ForceHandle) -> bool {
+        let count = self.generators.len() + self.springs.len();
+        self.generators.retain(|(h, _, _)| *h != handle);
+        self.springs.retain(|(h, _)| *h != handle);
+        count != self.generators.len() + self.springs.len()
+    }
+
+    pub fn spring_mut(&mut self, handle: ForceHandle) -> Option<&mut Spring> {
+        self.springs
+            .iter_mut()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, s)| s)
+    }
+
+    fn next_handle(&mut self) -> ForceHandle {
+        self.next_handle += 1;
+        ForceHandle(self.next_handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spring_force() {
+        let anchor = Vec2::new(0.0, 0.0);
+        let stretched = Force::spring(2.0, 1.0, Vec2::new(3.0, 0.0), anchor);
+        assert!(stretched.nearly_eq(Vec2::new(-4.0, 0.0)));
+        let compressed = Force::spring(2.0, 1.0, Vec2::new(0.0, 0.5), anchor);
+        assert!(compressed.nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!(Force::spring(2.0, 0.0, anchor, anchor).nearly_zero());
+
+        let damping = Force::spring_damping(0.5, Vec2::new(2.0, 0.0), anchor, Vec2::new(4.0, 3.0));
+        assert!(damping.nearly_eq(Vec2::new(-2.0, 0.0)));
     }
 }
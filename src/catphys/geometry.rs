@@ -0,0 +1,301 @@
+// Turns point lists, like hand drawn level outlines, into polygons the collision code
+// can use: convex, wound like Shape::Rect and without repeated or collinear points.
+use crate::vec2::consts::PI;
+use crate::vec2::{Real, Vec2};
+
+// Positive when wound like Shape::Rect, which is clockwise on screen with y down.
+// https://en.wikipedia.org/wiki/Shoelace_formula
+pub fn signed_area(points: &[Vec2]) -> Real {
+    (0..points.len())
+        .map(|i| points[i].cross(points[(i + 1) % points.len()]))
+        .sum::<Real>()
+        * 0.5
+}
+
+pub fn normalize_winding(points: &mut [Vec2]) {
+    if signed_area(points) < 0.0 {
+        points.reverse();
+    }
+}
+
+// Wound like Shape::Rect and turning the same way at every corner, exactly once around.
+// Collinear points don't count as corners.
+pub fn is_convex(points: &[Vec2]) -> bool {
+    turns_convex(points, false)
+}
+
+fn turns_convex(points: &[Vec2], allow_straight: bool) -> bool {
+    let n = points.len();
+    if n < 3 {
+        return false;
+    }
+    let mut total = 0.0;
+    for i in 0..n {
+        let (a, b, c) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+        let (in_edge, out_edge) = (b - a, c - b);
+        let turn = in_edge.cross(out_edge);
+        if turn < 0.0 || (turn == 0.0 && !allow_straight) {
+            return false;
+        }
+        total += turn.atan2(in_edge.dot(out_edge));
+    }
+    // A star turns the right way at every corner but goes around more than once.
+    (total - 2.0 * PI).abs() < 0.01
+}
+
+// Smallest convex polygon around the points, wound like Shape::Rect, without collinear
+// points. Fewer than 3 points if they're all on one line.
+// https://en.wikibooks.org/wiki/Algorithm_Implementation/Geometry/Convex_hull/Monotone_chain
+pub fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    // One chain along the bottom of the sorted points and one back along the top, each
+    // dropping points where it doesn't turn the right way.
+    let mut hull: Vec<Vec2> = Vec::with_capacity(sorted.len() + 1);
+    let mut chain_start = 0;
+    for (i, &p) in sorted.iter().chain(sorted.iter().rev().skip(1)).enumerate() {
+        if i == sorted.len() {
+            chain_start = hull.len() - 1; // The top chain starts at the bottom one's end.
+        }
+        while hull.len() >= chain_start + 2 {
+            let (a, b) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+            if (b - a).cross(p - b) > 0.0 {
+                break;
+            }
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    hull.pop(); // The first point again.
+    hull
+}
+
+// Removes points closer than tolerance to the point before them or to the line between
+// their neighbours, which also takes out thin spikes.
+pub fn simplify(points: &[Vec2], tolerance: Real) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    while points.len() >= 3 {
+        let n = points.len();
+        let redundant = (0..n).find(|&i| {
+            let (prev, p, next) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            let line = next - prev;
+            let off_line = if line.magnitude2() > 0.0 {
+                line.cross(p - prev).abs() / line.magnitude()
+            } else {
+                (p - prev).magnitude()
+            };
+            (p - prev).magnitude() <= tolerance || off_line <= tolerance
+        });
+        match redundant {
+            Some(i) => {
+                points.remove(i);
+            }
+            None => break,
+        }
+    }
+    points
+}
+
+// Splits a simple polygon, concave or not, into convex pieces wound like Shape::Rect.
+// Cuts it into triangles and then joins neighbouring ones back together as long as they
+// stay convex, which gives at most four times the fewest pieces possible.
+// https://en.wikipedia.org/wiki/Polygon_triangulation#Ear_clipping_method
+// https://en.wikipedia.org/wiki/Polygon_partition
+pub fn decompose(points: &[Vec2]) -> Vec<Vec<Vec2>> {
+    let mut points = simplify(points, 0.0);
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    normalize_winding(&mut points);
+
+    let mut pieces: Vec<Vec<usize>> = triangulate(&points);
+    let to_points = |piece: &[usize]| -> Vec<Vec2> { piece.iter().map(|&i| points[i]).collect() };
+    'merge: loop {
+        for i in 0..pieces.len() {
+            for j in i + 1..pieces.len() {
+                let Some(merged) = merge(&pieces[i], &pieces[j]) else {
+                    continue;
+                };
+                if turns_convex(&to_points(&merged), true) {
+                    pieces[i] = merged;
+                    pieces.swap_remove(j);
+                    continue 'merge;
+                }
+            }
+        }
+        break;
+    }
+    // Joining triangles along a straight line leaves a point in the middle of an edge.
+    pieces
+        .iter()
+        .map(|piece| simplify(&to_points(piece), 0.0))
+        .collect()
+}
+
+// Triangles as indices into points, which are wound like Shape::Rect.
+fn triangulate(points: &[Vec2]) -> Vec<Vec<usize>> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            (
+                points[remaining[(i + n - 1) % n]],
+                points[remaining[i]],
+                points[remaining[(i + 1) % n]],
+            )
+        };
+        // An ear is a convex corner with no other point inside the triangle it cuts off.
+        // Self intersecting outlines might not have one, then any corner has to do.
+        let ear = (0..n)
+            .find(|&i| {
+                let (a, b, c) = corner(i);
+                (b - a).cross(c - b) > 0.0
+                    && remaining.iter().all(|&p| {
+                        let p = points[p];
+                        p == a || p == b || p == c || !in_triangle(a, b, c, p)
+                    })
+            })
+            .unwrap_or(0);
+        triangles.push(vec![
+            remaining[(ear + n - 1) % n],
+            remaining[ear],
+            remaining[(ear + 1) % n],
+        ]);
+        remaining.remove(ear);
+    }
+    triangles.push(remaining);
+    triangles
+}
+
+// Including the edges, for a triangle wound like Shape::Rect.
+fn in_triangle(a: Vec2, b: Vec2, c: Vec2, p: Vec2) -> bool {
+    (b - a).cross(p - a) >= 0.0 && (c - b).cross(p - b) >= 0.0 && (a - c).cross(p - c) >= 0.0
+}
+
+// Two pieces sharing an edge as one, going around a up to the shared edge and then
+// around b.
+fn merge(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    let (na, nb) = (a.len(), b.len());
+    for i in 0..na {
+        let (u, v) = (a[i], a[(i + 1) % na]);
+        // Neighbours go along the shared edge the other way.
+        let Some(j) = (0..nb).find(|&j| b[j] == v && b[(j + 1) % nb] == u) else {
+            continue;
+        };
+        let around_a = (0..na).map(|k| a[(i + 1 + k) % na]);
+        let around_b = (0..nb - 2).map(|k| b[(j + 2 + k) % nb]);
+        return Some(around_a.chain(around_b).collect());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Vec<Vec2> {
+        vec![
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn hull_and_winding() {
+        assert_eq!(signed_area(&square()), 4.0);
+        assert!(is_convex(&square()));
+
+        // Inside, on an edge and repeated points don't end up in the hull.
+        let mut points = square();
+        points.extend([
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+        ]);
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 4);
+        assert!(is_convex(&hull));
+        assert_eq!(signed_area(&hull), 4.0);
+
+        let mut backwards: Vec<Vec2> = square().into_iter().rev().collect();
+        assert!(!is_convex(&backwards));
+        normalize_winding(&mut backwards);
+        assert!(is_convex(&backwards));
+
+        // Every corner turns the same way, but it goes around twice.
+        let star: Vec<Vec2> = (0..5)
+            .map(|i| Vec2::new(0.0, -1.0).rotate(i as Real * 4.0 * PI / 5.0))
+            .collect();
+        assert!(!is_convex(&star));
+        assert_eq!(convex_hull(&star).len(), 5);
+    }
+
+    #[test]
+    fn simplifies_outlines() {
+        let points = vec![
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(0.0, -1.001), // Nearly on the top edge.
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, -1.0), // Repeated.
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ];
+        assert_eq!(simplify(&points, 0.01), square());
+        assert_eq!(simplify(&points, 0.0).len(), 5);
+    }
+
+    #[test]
+    fn decomposes_concave_outlines() {
+        // An L, given the other way around.
+        let l: Vec<Vec2> = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, -3.0),
+            Vec2::new(1.0, -3.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(3.0, -1.0),
+            Vec2::new(3.0, 0.0),
+        ]
+        .into_iter()
+        .rev()
+        .collect();
+        assert!(signed_area(&l) < 0.0);
+        let pieces = decompose(&l);
+        assert_eq!(pieces.len(), 2);
+        assert!(pieces.iter().all(|p| is_convex(p)));
+        let area: Real = pieces.iter().map(|p| signed_area(p)).sum();
+        assert!((area - 5.0).abs() < 0.0001);
+
+        // A comb with three teeth needs at least three pieces.
+        let comb = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, -2.0),
+            Vec2::new(1.0, -2.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(2.0, -1.0),
+            Vec2::new(2.0, -2.0),
+            Vec2::new(3.0, -2.0),
+            Vec2::new(3.0, -1.0),
+            Vec2::new(4.0, -1.0),
+            Vec2::new(4.0, -2.0),
+            Vec2::new(5.0, -2.0),
+            Vec2::new(5.0, 0.0),
+        ];
+        let pieces = decompose(&comb);
+        assert!((3..=5).contains(&pieces.len()));
+        assert!(pieces.iter().all(|p| is_convex(p)));
+        let area: Real = pieces.iter().map(|p| signed_area(p)).sum();
+        assert!((area - signed_area(&comb)).abs() < 0.0001);
+
+        let pieces = decompose(&square());
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(signed_area(&pieces[0]), 4.0);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::vec2::Real;
+
+// Groups of bodies connected by contacts or joints. A group only goes to sleep as a
+// whole, so a resting pile doesn't fall apart when one of its bodies is still moving.
+// https://en.wikipedia.org/wiki/Disjoint-set_data_structure
+pub(crate) struct Islands {
+    parent: Vec<usize>,
+}
+
+impl Islands {
+    pub(crate) fn new(bodies: usize) -> Self {
+        Self {
+            parent: (0..bodies).collect(),
+        }
+    }
+
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+
+    // Island id, the same for every body in the island.
+    pub(crate) fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            // Path halving keeps the trees flat.
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+}
+
+// When bodies are considered resting, velocities in World units per second.
+#[derive(Copy, Clone, Debug)]
+pub struct SleepSettings {
+    pub enabled: bool,
+    pub linear_velocity: Real,
+    pub angular_velocity: Real,
+    pub time: Real, // How long a whole island has to rest before it sleeps.
+}
+
+impl Default for SleepSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            linear_velocity: 0.05,
+            angular_velocity: 0.05,
+            time: 0.5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_islands() {
+        let mut islands = Islands::new(6);
+        islands.union(0, 1);
+        islands.union(2, 3);
+        islands.union(1, 3);
+        assert_eq!(islands.find(0), islands.find(2));
+        assert_ne!(islands.find(0), islands.find(4));
+        assert_ne!(islands.find(4), islands.find(5));
+    }
+}
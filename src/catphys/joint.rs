@@ -0,0 +1,477 @@
+use crate::physics::Physics;
+use crate::solver::inverse_or_zero;
+use crate::vec2::{Mat2, Real, Rot2, Vec2};
+use crate::world::BodyHandle;
+
+// Anchors are points in body space, a joint with the world is a joint with an infinite
+// mass body. Reference angles are the rotation of b minus the rotation of a to keep.
+#[derive(Copy, Clone, Debug)]
+pub enum JointKind {
+    // Keeps the anchors length apart, a rope only keeps them from going further apart.
+    Distance {
+        local_a: Vec2,
+        local_b: Vec2,
+        length: Real,
+        rope: bool,
+    },
+    // Pins the anchors together, the bodies turn freely around the pin.
+    Revolute {
+        local_a: Vec2,
+        local_b: Vec2,
+    },
+    // Anchor b slides along axis (in body a space) through anchor a, no relative turning.
+    Prismatic {
+        local_a: Vec2,
+        local_b: Vec2,
+        axis: Vec2,
+        reference_angle: Real,
+    },
+    // Glues the bodies together.
+    Weld {
+        local_a: Vec2,
+        local_b: Vec2,
+        reference_angle: Real,
+    },
+    // Drives the angular velocity of b relative to a towards speed, using at most
+    // max_torque. Together with a revolute joint this makes a wheel.
+    Motor {
+        speed: Real,
+        max_torque: Real,
+    },
+}
+
+// Velocity constraints between two bodies, solved in the same iterations as the contacts.
+// Position errors are fed back into the velocities with Baumgarte stabilization.
+// https://box2d.org/files/ErinCatto_ModelingAndSolvingConstraints_GDC2009.pdf
+pub struct Joint {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub kind: JointKind,
+    pub collide_connected: bool, // Off by default, so the two bodies don't collide.
+
+    // Set up by prepare every step.
+    ia: usize,
+    ib: usize,
+    ra: Vec2,
+    rb: Vec2,
+    axis: Vec2, // Of the one dimensional linear constraint, with its angular parts.
+    sa: Real,
+    sb: Real,
+    point_bias: Vec2,
+    axial_bias: Real,
+    angular_bias: Real,
+    active: bool, // A slack rope does nothing.
+    max_angular_impulse: Real,
+
+    // Accumulated over the iterations and kept for warm starting the next step.
+    point_impulse: Vec2,
+    axial_impulse: Real,
+    angular_impulse: Real,
+}
+
+impl Joint {
+    pub fn new(a: BodyHandle, b: BodyHandle, kind: JointKind) -> Self {
+        Self {
+            a,
+            b,
+            kind,
+            collide_connected: false,
+            ia: 0,
+            ib: 0,
+            ra: Vec2::default(),
+            rb: Vec2::default(),
+            axis: Vec2::default(),
+            sa: 0.0,
+            sb: 0.0,
+            point_bias: Vec2::default(),
+            axial_bias: 0.0,
+            angular_bias: 0.0,
+            active: true,
+            max_angular_impulse: Real::INFINITY,
+            point_impulse: Vec2::default(),
+            axial_impulse: 0.0,
+            angular_impulse: 0.0,
+        }
+    }
+
+    // ia and ib index the bodies slice, baumgarte is the fraction of the position error
+    // removed per step.
+    pub(crate) fn prepare(
+        &mut self,
+        bodies: &[Physics],
+        ia: usize,
+        ib: usize,
+        dt: Real,
+        baumgarte: Real,
+    ) {
+        let (pa, pb) = (&bodies[ia], &bodies[ib]);
+        let bias = baumgarte / dt;
+        self.ia = ia;
+        self.ib = ib;
+        self.active = true;
+        self.max_angular_impulse = Real::INFINITY;
+
+        let (local_a, local_b) = match self.kind {
+            JointKind::Distance {
+                local_a, local_b, ..
+            }
+            | JointKind::Revolute { local_a, local_b }
+            | JointKind::Prismatic {
+                local_a, local_b, ..
+            }
+            | JointKind::Weld {
+                local_a, local_b, ..
+            } => (local_a, local_b),
+            JointKind::Motor { .. } => (Vec2::default(), Vec2::default()),
+        };
+        let (rot_a, rot_b) = (Rot2::new(pa.rotation), Rot2::new(pb.rotation));
+        self.ra = rot_a.rotate(local_a);
+        self.rb = rot_b.rotate(local_b);
+        let d = (pb.position + self.rb) - (pa.position + self.ra);
+        let angle = pb.rotation - pa.rotation;
+
+        match self.kind {
+            JointKind::Distance { length, rope, .. } => {
+                let distance = d.magnitude();
+                self.axis = d.unit();
+                self.sa = self.ra.cross(self.axis);
+                self.sb = self.rb.cross(self.axis);
+                self.axial_bias = bias * (distance - length);
+                self.active = !rope || distance > length;
+            }
+            JointKind::Revolute { .. } => {
+                self.point_bias = d * bias;
+            }
+            JointKind::Prismatic {
+                axis,
+                reference_angle,
+                ..
+            } => {
+                // Only the sideways offset is constrained, d also moves the point on
+                // body a the constraint acts at.
+                self.axis = rot_a.rotate(axis).unit().normal_positive();
+                self.sa = (d + self.ra).cross(self.axis);
+                self.sb = self.rb.cross(self.axis);
+                self.axial_bias = bias * d.dot(self.axis);
+                self.angular_bias = bias * (angle - reference_angle);
+            }
+            JointKind::Weld {
+                reference_angle, ..
+            } => {
+                self.point_bias = d * bias;
+                self.angular_bias = bias * (angle - reference_angle);
+            }
+            JointKind::Motor { speed, max_torque } => {
+                self.angular_bias = -speed;
+                self.max_angular_impulse = max_torque * dt;
+            }
+        }
+
+        if !self.active {
+            self.axial_impulse = 0.0;
+        }
+    }
+
+    pub(crate) fn warm_start(&self, bodies: &mut [Physics]) {
+        self.apply_point(bodies, self.point_impulse);
+        self.apply_axial(bodies, self.axial_impulse);
+        self.apply_angular(bodies, self.angular_impulse);
+    }
+
+    pub(crate) fn solve(&mut self, bodies: &mut [Physics]) {
+        match self.kind {
+            JointKind::Distance { rope, .. } => {
+                if self.active {
+                    self.solve_axial(bodies, rope);
+                }
+            }
+            JointKind::Revolute { .. } => self.solve_point(bodies),
+            JointKind::Prismatic { .. } => {
+                self.solve_angular(bodies);
+                self.solve_axial(bodies, false);
+            }
+            JointKind::Weld { .. } => {
+                self.solve_angular(bodies);
+                self.solve_point(bodies);
+            }
+            JointKind::Motor { .. } => self.solve_angular(bodies),
+        }
+    }
+
+    // Impulses are applied to b, and the opposite to a.
+    fn solve_point(&mut self, bodies: &mut [Physics]) {
+        let (pa, pb) = (&bodies[self.ia], &bodies[self.ib]);
+        let (ra, rb) = (self.ra, self.rb);
+        let relative_velocity = pb.velocity + Vec2::scalar_cross(pb.angular_velocity, rb)
+            - pa.velocity
+            - Vec2::scalar_cross(pa.angular_velocity, ra);
+
+        // 2x2 effective mass matrix, symmetric.
+        let (ma, mb, ia, ib) = (
+            pa.inverse_mass,
+            pb.inverse_mass,
+            pa.inverse_angular_mass,
+            pb.inverse_angular_mass,
+        );
+        let k11 = ma + mb + ia * ra.y * ra.y + ib * rb.y * rb.y;
+        let k12 = -ia * ra.x * ra.y - ib * rb.x * rb.y;
+        let k22 = ma + mb + ia * ra.x * ra.x + ib * rb.x * rb.x;
+
+        let impulse = -Mat2::new(k11, k12, k12, k22).solve(relative_velocity + self.point_bias);
+        self.point_impulse += impulse;
+        self.apply_point(bodies, impulse);
+    }
+
+    fn solve_axial(&mut self, bodies: &mut [Physics], rope: bool) {
+        let (pa, pb) = (&bodies[self.ia], &bodies[self.ib]);
+        let velocity = self.axis.dot(pb.velocity - pa.velocity) + pb.angular_velocity * self.sb
+            - pa.angular_velocity * self.sa;
+        let mass = inverse_or_zero(
+            pa.inverse_mass
+                + pb.inverse_mass
+                + pa.inverse_angular_mass * self.sa * self.sa
+                + pb.inverse_angular_mass * self.sb * self.sb,
+        );
+
+        let mut impulse = -mass * (velocity + self.axial_bias);
+        if rope {
+            // Ropes only pull.
+            let old = self.axial_impulse;
+            self.axial_impulse = Real::min(old + impulse, 0.0);
+            impulse = self.axial_impulse - old;
+        } else {
+            self.axial_impulse += impulse;
+        }
+        self.apply_axial(bodies, impulse);
+    }
+
+    fn solve_angular(&mut self, bodies: &mut [Physics]) {
+        let (pa, pb) = (&bodies[self.ia], &bodies[self.ib]);
+        let velocity = pb.angular_velocity - pa.angular_velocity;
+        let mass = inverse_or_zero(pa.inverse_angular_mass + pb.inverse_angular_mass);
+
+        let old = self.angular_impulse;
+        self.angular_impulse = Real::clamp(
+            old - mass * (velocity + self.angular_bias),
+            -self.max_angular_impulse,
+            self.max_angular_impulse,
+        );
+        self.apply_angular(bodies, self.angular_impulse - old);
+    }
+
+    fn apply_point(&self, bodies: &mut [Physics], impulse: Vec2) {
+        let pa = &mut bodies[self.ia];
+        pa.velocity -= impulse * pa.inverse_mass;
+        pa.angular_velocity -= self.ra.cross(impulse) * pa.inverse_angular_mass;
+        let pb = &mut bodies[self.ib];
+        pb.velocity += impulse * pb.inverse_mass;
+        pb.angular_velocity += self.rb.cross(impulse) * pb.inverse_angular_mass;
+    }
+
+    fn apply_axial(&self, bodies: &mut [Physics], impulse: Real) {
+        let pa = &mut bodies[self.ia];
+        pa.velocity -= self.axis * (impulse * pa.inverse_mass);
+        pa.angular_velocity -= self.sa * impulse * pa.inverse_angular_mass;
+        let pb = &mut bodies[self.ib];
+        pb.velocity += self.axis * (impulse * pb.inverse_mass);
+        pb.angular_velocity += self.sb * impulse * pb.inverse_angular_mass;
+    }
+
+    fn apply_angular(&self, bodies: &mut [Physics], impulse: Real) {
+        bodies[self.ia].angular_velocity -= impulse * bodies[self.ia].inverse_angular_mass;
+        bodies[self.ib].angular_velocity += impulse * bodies[self.ib].inverse_angular_mass;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::{Drag, ForceTarget};
+    use crate::shape::Shape;
+    use crate::world::World;
+
+    fn anchor(world: &mut World, x: Real, y: Real) -> BodyHandle {
+        world.add_body(
+            Physics::new_static(Vec2::new(x, y), 0.0),
+            Shape::Circle { radius: 0.1 },
+        )
+    }
+
+    fn box_body(world: &mut World, x: Real, y: Real) -> BodyHandle {
+        let shape = Shape::Rect { w: 0.5, h: 0.5 };
+        world.add_body(
+            Physics::new(Vec2::new(x, y), 1.0, shape.rotational_inertia(), 0.0),
+            shape,
+        )
+    }
+
+    fn run(world: &mut World, steps: usize) {
+        for _ in 0..steps {
+            world.step(1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn distance_keeps_pendulum_length() {
+        let mut world = World::default();
+        let pivot = anchor(&mut world, 0.0, 0.0);
+        let bob = box_body(&mut world, 2.0, 0.0);
+        let length = 2.0;
+        world.add_joint(Joint::new(
+            pivot,
+            bob,
+            JointKind::Distance {
+                local_a: Vec2::default(),
+                local_b: Vec2::default(),
+                length,
+                rope: false,
+            },
+        ));
+
+        let mut lowest: Real = 0.0;
+        for _ in 0..300 {
+            world.step(1.0 / 60.0);
+            let position = world.physics(bob).unwrap().position;
+            assert!((position.magnitude() - length).abs() < 0.05);
+            lowest = lowest.max(position.y);
+        }
+        assert!(lowest > 1.9);
+    }
+
+    #[test]
+    fn slack_rope_lets_go() {
+        let mut world = World::default();
+        let pivot = anchor(&mut world, 0.0, 0.0);
+        let bob = box_body(&mut world, 0.0, 1.0);
+        world.add_joint(Joint::new(
+            pivot,
+            bob,
+            JointKind::Distance {
+                local_a: Vec2::default(),
+                local_b: Vec2::default(),
+                length: 3.0,
+                rope: true,
+            },
+        ));
+
+        run(&mut world, 20);
+        // Falls freely until the rope is taut.
+        assert!(world.physics(bob).unwrap().velocity.y > 3.0);
+        run(&mut world, 300);
+        assert!((world.physics(bob).unwrap().position.y - 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn revolute_chain_hangs_together() {
+        let mut world = World::default();
+        let mut previous = anchor(&mut world, 0.0, 0.0);
+        let mut links = Vec::new();
+        for i in 0..10 {
+            let link = box_body(&mut world, 0.5 * (i + 1) as Real, 0.0);
+            let local_a = if i == 0 {
+                Vec2::default()
+            } else {
+                Vec2::new(0.25, 0.0)
+            };
+            world.add_joint(Joint::new(
+                previous,
+                link,
+                JointKind::Revolute {
+                    local_a,
+                    local_b: Vec2::new(-0.25, 0.0),
+                },
+            ));
+            links.push(link);
+            previous = link;
+        }
+        // So it stops swinging.
+        world
+            .forces
+            .add(ForceTarget::All, Drag { k1: 1.0, k2: 0.0 });
+
+        run(&mut world, 1200);
+
+        // Hangs straight down from the anchor.
+        let last = world.physics(*links.last().unwrap()).unwrap();
+        assert!(last.position.x.abs() < 0.1);
+        assert!((last.position.y - 4.75).abs() < 0.1);
+    }
+
+    #[test]
+    fn prismatic_slides_along_axis() {
+        let mut world = World::default();
+        let base = anchor(&mut world, 0.0, 0.0);
+        let slider = box_body(&mut world, 0.0, 0.0);
+        // 45 degrees down to the right.
+        world.add_joint(Joint::new(
+            base,
+            slider,
+            JointKind::Prismatic {
+                local_a: Vec2::default(),
+                local_b: Vec2::default(),
+                axis: Vec2::new(1.0, 1.0),
+                reference_angle: 0.0,
+            },
+        ));
+
+        run(&mut world, 60);
+
+        let physics = world.physics(slider).unwrap();
+        assert!(physics.position.y > 1.0);
+        assert!((physics.position.x - physics.position.y).abs() < 0.01);
+        assert!(physics.rotation.abs() < 0.01);
+    }
+
+    #[test]
+    fn weld_holds_a_cantilever() {
+        let mut world = World::default();
+        let wall = anchor(&mut world, 0.0, 0.0);
+        let beam = box_body(&mut world, 1.0, 0.0);
+        world.add_joint(Joint::new(
+            wall,
+            beam,
+            JointKind::Weld {
+                local_a: Vec2::default(),
+                local_b: Vec2::new(-1.0, 0.0),
+                reference_angle: 0.0,
+            },
+        ));
+
+        run(&mut world, 300);
+
+        let physics = world.physics(beam).unwrap();
+        assert!((physics.position - Vec2::new(1.0, 0.0)).magnitude() < 0.05);
+        assert!(physics.rotation.abs() < 0.05);
+    }
+
+    #[test]
+    fn motor_spins_wheel() {
+        let mut world = World::new(Vec2::default());
+        let axle = anchor(&mut world, 0.0, 0.0);
+        let wheel = box_body(&mut world, 0.0, 0.0);
+        world.add_joint(Joint::new(
+            axle,
+            wheel,
+            JointKind::Revolute {
+                local_a: Vec2::default(),
+                local_b: Vec2::default(),
+            },
+        ));
+        world.add_joint(Joint::new(
+            axle,
+            wheel,
+            JointKind::Motor {
+                speed: 3.0,
+                max_torque: 1.0,
+            },
+        ));
+
+        // Torque limited, so it takes a while to get up to speed.
+        run(&mut world, 1);
+        assert!(world.physics(wheel).unwrap().angular_velocity < 1.0);
+        run(&mut world, 120);
+        let physics = world.physics(wheel).unwrap();
+        assert!((physics.angular_velocity - 3.0).abs() < 0.01);
+        assert!(physics.position.magnitude() < 0.01);
+    }
+}
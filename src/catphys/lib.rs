@@ -1,11 +1,35 @@
+pub mod broadphase;
 pub mod collision;
+pub mod filter;
 pub mod force;
+pub mod geometry;
+pub mod island;
+pub mod joint;
+pub mod manifold;
 pub mod physics;
 pub mod shape;
+pub mod solver;
+pub mod timestep;
+pub mod toi;
 pub mod vec2;
+pub mod world;
 
+pub use crate::broadphase::{Aabb, Broadphase};
 pub use crate::collision::Collision;
-pub use crate::force::Force;
-pub use crate::physics::Physics;
-pub use crate::shape::Shape;
-pub use crate::vec2::Vec2;
+pub use crate::filter::Filter;
+pub use crate::force::{
+    AnchoredSpring, Buoyancy, Drag, Force, ForceGenerator, ForceHandle, ForceRegistry, ForceTarget,
+    Gravity, Spring,
+};
+pub use crate::island::SleepSettings;
+pub use crate::joint::{Joint, JointKind};
+pub use crate::manifold::{ContactPoint, Manifold};
+pub use crate::physics::{BodyType, Integrator, Physics};
+pub use crate::shape::{ChildShape, MassProperties, Shape};
+pub use crate::solver::ContactSolver;
+pub use crate::timestep::FixedTimestep;
+pub use crate::toi::{time_of_impact, Sweep};
+pub use crate::vec2::{Mat2, Real, Rot2, Transform2, Vec2};
+pub use crate::world::{
+    BodyHandle, Contact, ContactEvent, ContactPhase, JointHandle, RayHit, ShapeHit, World,
+};
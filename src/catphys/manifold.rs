@@ -0,0 +1,67 @@
+use crate::vec2::{Real, Vec2};
+
+pub const MAX_CONTACT_POINTS: usize = 2;
+
+#[derive(Default, Copy, Clone, Debug)]
+pub struct ContactPoint {
+    pub position: Vec2, // World space, halfway between the two surfaces.
+    pub depth: Real,
+    pub id: u32,   // Which features of the two shapes touch, stable between frames.
+    pub part: u32, // Which pair of parts of compound shapes, 0 for other shapes.
+}
+
+// All the points where a pair of bodies touch, sharing one normal.
+#[derive(Copy, Clone, Debug)]
+pub struct Manifold {
+    normal: Vec2, // Points from body a to body b.
+    points: [ContactPoint; MAX_CONTACT_POINTS],
+    count: usize,
+}
+
+impl Manifold {
+    pub fn new(normal: Vec2) -> Self {
+        Self {
+            normal,
+            points: [ContactPoint::default(); MAX_CONTACT_POINTS],
+            count: 0,
+        }
+    }
+
+    // Points past MAX_CONTACT_POINTS are ignored.
+    pub fn add_point(&mut self, position: Vec2, depth: Real, id: u32) {
+        if self.count < MAX_CONTACT_POINTS {
+            self.points[self.count] = ContactPoint {
+                position,
+                depth,
+                id,
+                part: 0,
+            };
+            self.count += 1;
+        }
+    }
+
+    pub fn normal(&self) -> Vec2 {
+        self.normal
+    }
+
+    pub fn points(&self) -> &[ContactPoint] {
+        &self.points[..self.count]
+    }
+
+    // Depth of the deepest point.
+    pub fn depth(&self) -> Real {
+        self.points().iter().map(|p| p.depth).fold(0.0, Real::max)
+    }
+
+    // Marks the points as coming from another pair of parts of compound shapes, so the
+    // points of one pair of bodies can be told apart even when their ids are the same.
+    pub(crate) fn set_part(&mut self, part: u32) {
+        for point in self.points[..self.count].iter_mut() {
+            point.part = part;
+        }
+    }
+
+    pub fn flip(&mut self) {
+        self.normal = -self.normal;
+    }
+}
@@ -1,67 +1,524 @@
-use crate::vec2::Vec2;
+use crate::filter::Filter;
+use crate::shape::Shape;
+use crate::solver::inverse_or_zero;
+use crate::vec2::{Real, Transform2, Vec2};
+
+// How velocities and positions are advanced over a step.
+// https://gafferongames.com/post/integration_basics/
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub enum Integrator {
+    // Velocity first, then position with the new velocity. Cheap and stable, energy
+    // wobbles around the right value instead of drifting away.
+    #[default]
+    SemiImplicitEuler,
+    // Second order, exact for constant accelerations like gravity.
+    VelocityVerlet,
+    // Fourth order Runge-Kutta, four acceleration evaluations per step.
+    Rk4,
+}
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub enum BodyType {
+    // Moved by forces and contacts.
+    #[default]
+    Dynamic,
+    // Never moves, like the ground.
+    Static,
+    // Moves with whatever velocity it is given and pushes dynamic bodies out of the way,
+    // but forces and contacts don't affect it, like an elevator.
+    Kinematic,
+}
 
 pub struct Physics {
     pub position: Vec2,
     pub velocity: Vec2,
     pub forces: Vec2,
-    pub mass: f32,
-    pub inverse_mass: f32,
+    pub mass: Real,
+    pub inverse_mass: Real,
 
     // TODO: Perhaps split this struct into Physics and RotationalPhysics later.
-    pub rotation: f32,
-    pub angular_velocity: f32,
-    pub torque: f32,
-    pub angular_mass: f32,
-    pub inverse_angular_mass: f32,
+    pub rotation: Real,
+    pub angular_velocity: Real,
+    pub torque: Real,
+    pub angular_mass: Real,
+    pub inverse_angular_mass: Real,
+    body_type: BodyType, // Static and kinematic bodies have zero inverse masses.
+
+    // Where the body was before the last World::step, for interpolated rendering.
+    pub previous_position: Vec2,
+    pub previous_rotation: Real,
+
+    // Resting bodies are put to sleep by the World and skipped until something touches
+    // them or a force is applied.
+    sleeping: bool,
+    sleep_time: Real, // How long the body has been resting.
+    pub can_sleep: bool,
+
+    // Fast moving body checked for collisions along its whole path, not just where it
+    // ends up, so it can't pass through thin bodies.
+    pub bullet: bool,
+    // Overrides the World integrator for this body.
+    pub integrator: Option<Integrator>,
+    // Position change still owed by integrate_position, see integrate_velocity_with.
+    drift: Vec2,
+    angular_drift: Real,
 
-    pub restitution: f32,
+    pub restitution: Real,
+    // Coulomb friction coefficients, static applies while the contact isn't sliding.
+    pub static_friction: Real,
+    pub dynamic_friction: Real,
+    // Which other bodies this one collides with.
+    pub filter: Filter,
+    // Sensors report contacts without pushing anything, like trigger zones.
+    pub sensor: bool,
+    // One-way platforms only push bodies coming from this side, a body space direction.
+    // Bodies coming from anywhere else pass through, see World::disable_contact.
+    pub one_way: Option<Vec2>,
 }
 
 impl Physics {
-    pub fn new(position: Vec2, mass: f32, rotational_inertia: f32, restitution: f32) -> Self {
+    pub fn new(position: Vec2, mass: Real, rotational_inertia: Real, restitution: Real) -> Self {
+        Self::with_body_type(
+            position,
+            mass,
+            rotational_inertia,
+            restitution,
+            BodyType::Dynamic,
+        )
+    }
+
+    // Static and kinematic bodies get no inverse masses whatever their mass.
+    fn with_body_type(
+        position: Vec2,
+        mass: Real,
+        rotational_inertia: Real,
+        restitution: Real,
+        body_type: BodyType,
+    ) -> Self {
         let angular_mass = rotational_inertia * mass;
+        let (inverse_mass, inverse_angular_mass) = match body_type {
+            BodyType::Dynamic => (1.0 / mass, 1.0 / angular_mass),
+            BodyType::Static | BodyType::Kinematic => (0.0, 0.0),
+        };
 
         Self {
             position,
             velocity: Vec2::new(0.0, 0.0),
             forces: Vec2::new(0.0, 0.0),
             mass,
-            inverse_mass: 1.0 / mass,
+            inverse_mass,
             rotation: 0.0,
             angular_velocity: 0.0,
             torque: 0.0,
             angular_mass,
-            inverse_angular_mass: 1.0 / angular_mass,
+            inverse_angular_mass,
+            body_type,
+            previous_position: position,
+            previous_rotation: 0.0,
+            sleeping: false,
+            sleep_time: 0.0,
+            can_sleep: true,
+            bullet: false,
+            integrator: None,
+            drift: Vec2::new(0.0, 0.0),
+            angular_drift: 0.0,
             //https://phys.libretexts.org/Courses/Prince_Georges_Community_College/General_Physics_I%3A_Classical_Mechanics/31%3A_Collisions/31.01%3A_The_Coefficient_of_Restitution
             restitution, // 1.0 (bouncy) elastic colision, 0.0 inelastic collision
+            //https://en.wikipedia.org/wiki/Friction#Coefficient_of_friction
+            static_friction: 0.5,
+            dynamic_friction: 0.3,
+            filter: Filter::default(),
+            sensor: false,
+            one_way: None,
         }
     }
 
+    // Dynamic body weighing what the shape does at density, in kg per square World unit.
+    // The shape's center of mass should be at the body position, see Shape::centroid.
+    // None if that weighs nothing, like segments and chains or a density that isn't
+    // positive, use new_static for those.
+    pub fn from_shape(
+        position: Vec2,
+        shape: &Shape,
+        density: Real,
+        restitution: Real,
+    ) -> Option<Self> {
+        let mass = shape.mass_properties(density).mass;
+        (mass.is_finite() && mass > 0.0)
+            .then(|| Self::new(position, mass, shape.rotational_inertia(), restitution))
+    }
+
+    // Mass and rotational inertia don't matter for static and kinematic bodies.
+    pub fn new_static(position: Vec2, restitution: Real) -> Self {
+        Self::with_body_type(position, 0.0, 0.0, restitution, BodyType::Static)
+    }
+
+    pub fn new_kinematic(position: Vec2, restitution: Real) -> Self {
+        Self::with_body_type(position, 0.0, 0.0, restitution, BodyType::Kinematic)
+    }
+
+    pub fn body_type(&self) -> BodyType {
+        self.body_type
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    // Also updates the inverse masses, a dynamic body gets them back from mass and
+    // angular_mass. A body needs a positive mass to become dynamic, otherwise nothing
+    // changes and false is returned. Without angular_mass it doesn't turn.
+    pub fn set_body_type(&mut self, body_type: BodyType) -> bool {
+        if body_type == BodyType::Dynamic && !(self.mass.is_finite() && self.mass > 0.0) {
+            return false;
+        }
+        self.wake();
+        self.body_type = body_type;
+        match body_type {
+            BodyType::Dynamic => {
+                self.inverse_mass = 1.0 / self.mass;
+                self.inverse_angular_mass = inverse_or_zero(self.angular_mass);
+            }
+            BodyType::Static | BodyType::Kinematic => {
+                self.inverse_mass = 0.0;
+                self.inverse_angular_mass = 0.0;
+                if body_type == BodyType::Static {
+                    self.velocity = Vec2::new(0.0, 0.0);
+                    self.angular_velocity = 0.0;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    pub fn wake(&mut self) {
+        if self.sleeping {
+            self.sleeping = false;
+            self.sleep_time = 0.0;
+        }
+    }
+
+    // Stops the body where it is, only dynamic bodies sleep.
+    pub fn sleep(&mut self) {
+        if !self.is_dynamic() {
+            return;
+        }
+        self.sleeping = true;
+        self.velocity = Vec2::new(0.0, 0.0);
+        self.angular_velocity = 0.0;
+        self.forces = Vec2::new(0.0, 0.0);
+        self.torque = 0.0;
+    }
+
+    // Time spent below the velocity thresholds, reset when the body moves faster or can't
+    // sleep at all.
+    pub(crate) fn update_sleep_time(&mut self, dt: Real, linear: Real, angular: Real) -> Real {
+        let resting =
+            self.velocity.magnitude2() <= linear * linear && self.angular_velocity.abs() <= angular;
+        self.sleep_time = if self.can_sleep && resting {
+            self.sleep_time + dt
+        } else {
+            0.0
+        };
+        self.sleep_time
+    }
+
+    // Forces and impulses wake the body up.
     pub fn apply_force(&mut self, force: Vec2) {
         self.forces += force;
+        if !force.nearly_zero() {
+            self.wake();
+        }
+    }
+
+    // Force applied at a world space point also adds torque.
+    pub fn apply_force_at(&mut self, force: Vec2, point: Vec2) {
+        self.apply_force(force);
+        self.torque += (point - self.position).cross(force);
     }
 
-    pub fn apply_torque(&mut self, torq: f32) {
+    pub fn apply_torque(&mut self, torq: Real) {
         self.torque += torq;
+        if torq != 0.0 {
+            self.wake();
+        }
     }
 
     pub fn apply_impulse(&mut self, impulse: Vec2) {
         self.velocity += impulse * self.inverse_mass;
+        if !impulse.nearly_zero() {
+            self.wake();
+        }
     }
 
-    pub fn integrate(&mut self, dt: f32) {
-        let acceleration = self.forces * self.inverse_mass;
-        self.velocity += acceleration * dt;
-        self.position += self.velocity * dt;
+    // Impulse applied at a world space point also changes the angular velocity.
+    pub fn apply_impulse_at(&mut self, impulse: Vec2, point: Vec2) {
+        self.apply_impulse(impulse);
+        self.angular_velocity += (point - self.position).cross(impulse) * self.inverse_angular_mass;
+    }
+
+    // From body space to World space, the sin and cos worked out once.
+    pub fn transform(&self) -> Transform2 {
+        Transform2::new(self.position, self.rotation)
+    }
+
+    // Velocity of a world space point moving along with the body.
+    pub fn velocity_at(&self, point: Vec2) -> Vec2 {
+        let r = point - self.position;
+        self.velocity + Vec2::scalar_cross(self.angular_velocity, r)
+    }
+
+    // Blends the previous and current state, alpha as in FixedTimestep::alpha.
+    pub fn interpolated_position(&self, alpha: Real) -> Vec2 {
+        self.previous_position * (1.0 - alpha) + self.position * alpha
+    }
+
+    pub fn interpolated_rotation(&self, alpha: Real) -> Real {
+        self.previous_rotation * (1.0 - alpha) + self.rotation * alpha
+    }
+
+    pub fn integrate(&mut self, dt: Real) {
+        self.integrate_velocity(dt);
+        self.integrate_position(dt);
+    }
+
+    // A whole step for a body outside of a World. acceleration(position, velocity) is
+    // added to the applied forces, so position and velocity dependent forces like springs
+    // and orbits can be evaluated at the points the integrator needs.
+    pub fn integrate_with<F>(&mut self, integrator: Integrator, dt: Real, acceleration: F)
+    where
+        F: Fn(Vec2, Vec2) -> Vec2,
+    {
+        if !self.is_dynamic() {
+            self.integrate_velocity(dt);
+            self.integrate_position(dt);
+            return;
+        }
+
+        let forced = self.forces * self.inverse_mass;
+        let acceleration = |x: Vec2, v: Vec2| forced + acceleration(x, v);
+        let (x, v) = (self.position, self.velocity);
+
+        match integrator {
+            Integrator::SemiImplicitEuler => {
+                self.velocity += acceleration(x, v) * dt;
+                self.position += self.velocity * dt;
+            }
+            Integrator::VelocityVerlet => {
+                let a0 = acceleration(x, v);
+                self.position += v * dt + a0 * (0.5 * dt * dt);
+                // Velocity dependent forces see the Euler estimate of the new velocity.
+                let a1 = acceleration(self.position, v + a0 * dt);
+                self.velocity += (a0 + a1) * (0.5 * dt);
+            }
+            Integrator::Rk4 => {
+                let (k1x, k1v) = (v, acceleration(x, v));
+                let (k2x, k2v) = {
+                    let v2 = v + k1v * (0.5 * dt);
+                    (v2, acceleration(x + k1x * (0.5 * dt), v2))
+                };
+                let (k3x, k3v) = {
+                    let v3 = v + k2v * (0.5 * dt);
+                    (v3, acceleration(x + k2x * (0.5 * dt), v3))
+                };
+                let (k4x, k4v) = {
+                    let v4 = v + k3v * dt;
+                    (v4, acceleration(x + k3x * dt, v4))
+                };
+                self.position += (k1x + k2x * 2.0 + k3x * 2.0 + k4x) * (dt / 6.0);
+                self.velocity += (k1v + k2v * 2.0 + k3v * 2.0 + k4v) * (dt / 6.0);
+            }
+        }
+
+        self.angular_velocity += self.torque * self.inverse_angular_mass * dt;
+        self.rotation += self.angular_velocity * dt;
 
         self.forces = Vec2::new(0.0, 0.0);
+        self.torque = 0.0;
     }
 
-    pub fn integrate_angular(&mut self, dt: f32) {
+    // Forces and torque to velocities. Contact constraints are solved between this and
+    // integrate_position.
+    pub fn integrate_velocity(&mut self, dt: Real) {
+        self.integrate_velocity_with(Integrator::SemiImplicitEuler, dt);
+    }
+
+    // Only the forces applied so far are known here, so Verlet and RK4 move the body by
+    // x += v * dt + a * dt^2 / 2 with those. Since the velocity already holds the a * dt,
+    // the -a * dt^2 / 2 left over is kept in drift and added by integrate_position, on top
+    // of whatever the contact solver did to the velocity. A World evaluates the forces
+    // during the step for Verlet and RK4 bodies, see World::integrate_in_stages, and
+    // integrate_with does the same outside of one.
+    // Static and kinematic bodies keep their velocities, the forces are dropped.
+    pub fn integrate_velocity_with(&mut self, integrator: Integrator, dt: Real) {
+        if !self.is_dynamic() {
+            self.forces = Vec2::new(0.0, 0.0);
+            self.torque = 0.0;
+            return;
+        }
+
+        let acceleration = self.forces * self.inverse_mass;
+        self.velocity += acceleration * dt;
+        self.drift = match integrator {
+            Integrator::SemiImplicitEuler => Vec2::new(0.0, 0.0),
+            Integrator::VelocityVerlet | Integrator::Rk4 => acceleration * (-0.5 * dt * dt),
+        };
+
         let angular_acc = self.torque * self.inverse_angular_mass;
         self.angular_velocity += angular_acc * dt;
-        self.rotation += self.angular_velocity * dt;
 
+        self.forces = Vec2::new(0.0, 0.0);
+        self.torque = 0.0;
+    }
+
+    // For integrators worked out elsewhere, see World::integrate_in_stages. The velocities
+    // are changed now and the rest of the moves are left for integrate_position.
+    pub(crate) fn set_step(
+        &mut self,
+        dt: Real,
+        velocity: Vec2,
+        angular_velocity: Real,
+        moved: Vec2,
+        turned: Real,
+    ) {
+        self.velocity = velocity;
+        self.angular_velocity = angular_velocity;
+        self.drift = moved - velocity * dt;
+        self.angular_drift = turned - angular_velocity * dt;
+        self.forces = Vec2::new(0.0, 0.0);
+        self.torque = 0.0;
+    }
+
+    // Torque to angular velocity to rotation, leaving the linear motion alone.
+    // Static and kinematic bodies have no inverse angular mass, torque doesn't turn them.
+    pub fn integrate_angular(&mut self, dt: Real) {
+        self.angular_velocity += self.torque * self.inverse_angular_mass * dt;
+        if self.body_type != BodyType::Static {
+            self.rotation += self.angular_velocity * dt;
+        }
         self.torque = 0.0;
     }
+
+    pub fn integrate_position(&mut self, dt: Real) {
+        if self.body_type == BodyType::Static {
+            return;
+        }
+        self.position += self.velocity * dt + self.drift;
+        self.rotation += self.angular_velocity * dt + self.angular_drift;
+        self.drift = Vec2::new(0.0, 0.0);
+        self.angular_drift = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Relative change in energy after a long run of a unit mass on a spring, starting at
+    // x = 1 at rest, period about 6.3 seconds.
+    fn spring_energy_drift(integrator: Integrator) -> Real {
+        let k = 1.0;
+        let energy =
+            |p: &Physics| 0.5 * p.velocity.magnitude2() + 0.5 * k * p.position.magnitude2();
+
+        let mut physics = Physics::new(Vec2::new(1.0, 0.0), 1.0, 1.0, 0.0);
+        let start = energy(&physics);
+        for _ in 0..10_000 {
+            physics.integrate_with(integrator, 0.05, |x, _| -x * k);
+        }
+        (energy(&physics) - start).abs() / start
+    }
+
+    // Elliptic orbit around a unit mass at the origin, G = 1.
+    fn orbit_energy_drift(integrator: Integrator) -> Real {
+        let energy = |p: &Physics| 0.5 * p.velocity.magnitude2() - 1.0 / p.position.magnitude();
+
+        let mut physics = Physics::new(Vec2::new(1.0, 0.0), 1.0, 1.0, 0.0);
+        physics.velocity = Vec2::new(0.0, 0.8);
+        let start = energy(&physics);
+        for _ in 0..10_000 {
+            physics.integrate_with(integrator, 0.01, |x, _| -x / x.magnitude().powi(3));
+        }
+        (energy(&physics) - start).abs() / start.abs()
+    }
+
+    #[test]
+    fn spring_energy_is_kept() {
+        assert!(spring_energy_drift(Integrator::SemiImplicitEuler) < 0.05);
+        assert!(spring_energy_drift(Integrator::VelocityVerlet) < 0.001);
+        assert!(spring_energy_drift(Integrator::Rk4) < 0.0001);
+    }
+
+    #[test]
+    fn orbit_energy_is_kept() {
+        assert!(orbit_energy_drift(Integrator::SemiImplicitEuler) < 0.01);
+        assert!(orbit_energy_drift(Integrator::VelocityVerlet) < 0.0001);
+        assert!(orbit_energy_drift(Integrator::Rk4) < 0.0001);
+    }
+
+    #[test]
+    fn static_and_kinematic_ignore_forces() {
+        let mut ground = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        assert_eq!(
+            (ground.inverse_mass, ground.inverse_angular_mass),
+            (0.0, 0.0)
+        );
+        ground.velocity = Vec2::new(1.0, 0.0);
+        ground.apply_force(Vec2::new(0.0, 100.0));
+        ground.apply_impulse_at(Vec2::new(0.0, 100.0), Vec2::new(1.0, 0.0));
+        ground.integrate(1.0);
+        assert_eq!(ground.position, Vec2::new(0.0, 0.0));
+        assert_eq!(ground.rotation, 0.0);
+
+        let mut elevator = Physics::new_kinematic(Vec2::new(0.0, 0.0), 0.0);
+        assert_eq!(
+            (elevator.inverse_mass, elevator.inverse_angular_mass),
+            (0.0, 0.0)
+        );
+        // Can't be pushed around without a mass.
+        assert!(!elevator.set_body_type(BodyType::Dynamic));
+        assert_eq!(elevator.body_type(), BodyType::Kinematic);
+        elevator.velocity = Vec2::new(0.0, -2.0);
+        elevator.apply_force(Vec2::new(0.0, 100.0));
+        elevator.integrate(0.5);
+        assert_eq!(elevator.position, Vec2::new(0.0, -1.0));
+        assert_eq!(elevator.velocity, Vec2::new(0.0, -2.0));
+
+        elevator.mass = 2.0;
+        elevator.angular_mass = 4.0;
+        assert!(elevator.set_body_type(BodyType::Dynamic));
+        assert_eq!(
+            (elevator.inverse_mass, elevator.inverse_angular_mass),
+            (0.5, 0.25)
+        );
+    }
+
+    #[test]
+    fn integrates_angular_motion_alone() {
+        let mut physics = Physics::new(Vec2::new(0.0, 0.0), 1.0, 1.0, 0.0);
+        physics.velocity = Vec2::new(1.0, 0.0);
+        physics.apply_torque(2.0);
+        physics.integrate_angular(0.5);
+        assert_eq!(physics.angular_velocity, 1.0);
+        assert_eq!(physics.rotation, 0.5);
+        assert_eq!(physics.torque, 0.0);
+        assert_eq!(physics.position, Vec2::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn verlet_is_exact_under_constant_force() {
+        let mut physics = Physics::new(Vec2::new(0.0, 0.0), 2.0, 1.0, 0.0);
+        for _ in 0..10 {
+            physics.apply_force(Vec2::new(0.0, 4.0));
+            physics.integrate_velocity_with(Integrator::VelocityVerlet, 0.1);
+            physics.integrate_position(0.1);
+        }
+        // x = a * t^2 / 2 with a = 2 and t = 1.
+        assert!((physics.position.y - 1.0).abs() < 1e-5);
+        assert!((physics.velocity.y - 2.0).abs() < 1e-5);
+    }
 }
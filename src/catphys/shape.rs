@@ -1,18 +1,617 @@
-use crate::vec2::Vec2;
+use crate::broadphase::Aabb;
+use crate::collision::Collision;
+use crate::geometry;
+use crate::physics::Physics;
+use crate::vec2::consts::PI;
+use crate::vec2::{Real, Transform2, Vec2};
 
 pub enum Shape {
-    Circle { radius: f32 },
-    Rect { w: f32, h: f32 }, // AABB
+    Circle { radius: Real },
+    Rect { w: Real, h: Real }, // AABB
+    // Vertices are relative to the body position and wound the same way as Rect
+    // (upper left, upper right, lower right, lower left on screen), so that
+    // edge.normal_positive() points out of the polygon. Has to be convex, Shape::polygon
+    // checks that.
     Polygon { verts: Vec<Vec2> },
+    // Upright rounded box: a vertical segment of the given length, grown by radius.
+    Capsule { length: Real, radius: Real },
+    // Line between two points relative to the body position, has no area. For terrain,
+    // see Shape::chain.
+    Segment { a: Vec2, b: Vec2 },
+    // Several shapes moving as one body, made with Shape::compound.
+    Compound { children: Vec<ChildShape> },
+}
+
+// Part of a compound shape, placed relative to the body position and rotation.
+pub struct ChildShape {
+    pub offset: Vec2,
+    pub rotation: Real,
+    pub shape: Shape,
+}
+
+// What a shape of some density weighs and how it turns, see Shape::mass_properties.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MassProperties {
+    pub mass: Real,
+    pub center: Vec2,  // Center of mass relative to the body position.
+    pub inertia: Real, // Around the body position, mass times World units squared.
 }
 
 //https://phys.libretexts.org/Bookshelves/College_Physics/
 impl Shape {
-    pub fn rotational_inertia(&self) -> f32 {
+    // Moves the children so their combined center of mass, by area, is at the body
+    // position. Also gives that center in the frame the children were placed in.
+    pub fn compound(mut children: Vec<ChildShape>) -> (Shape, Vec2) {
+        let center = Self::compound_centroid(&children);
+        for child in children.iter_mut() {
+            child.offset -= center;
+        }
+        (Shape::Compound { children }, center)
+    }
+
+    fn compound_centroid(children: &[ChildShape]) -> Vec2 {
+        let area: Real = children.iter().map(|c| c.shape.area()).sum();
+        if area <= 0.0 {
+            return Vec2::new(0.0, 0.0);
+        }
+        children.iter().fold(Vec2::new(0.0, 0.0), |sum, c| {
+            sum + (c.offset + c.shape.centroid().rotate(c.rotation)) * c.shape.area()
+        }) / area
+    }
+
+    // Convex polygon from points relative to the body position, in either winding.
+    // Repeated and collinear points are dropped. None if there's no area left or the
+    // points aren't convex, see Shape::decomposed.
+    pub fn polygon(points: &[Vec2]) -> Option<Shape> {
+        let mut verts = geometry::simplify(points, outline_tolerance(points));
+        geometry::normalize_winding(&mut verts);
+        geometry::is_convex(&verts).then_some(Shape::Polygon { verts })
+    }
+
+    // Any simple outline relative to the body position, concave or not, as a compound
+    // of convex polygons. Its center of mass is wherever the outline puts it, so this is
+    // meant for static bodies like level geometry. None if nothing is left or the outline
+    // crosses itself so that some piece isn't convex.
+    pub fn decomposed(outline: &[Vec2]) -> Option<Shape> {
+        let outline = geometry::simplify(outline, outline_tolerance(outline));
+        let pieces = geometry::decompose(&outline);
+        if pieces.is_empty() || !pieces.iter().all(|piece| geometry::is_convex(piece)) {
+            return None;
+        }
+        let children = pieces
+            .into_iter()
+            .map(|verts| ChildShape {
+                offset: Vec2::new(0.0, 0.0),
+                rotation: 0.0,
+                shape: Shape::Polygon { verts },
+            })
+            .collect();
+        Some(Shape::Compound { children })
+    }
+
+    // Segments joining the points one after another, as one static body.
+    pub fn chain(points: &[Vec2]) -> Shape {
+        let children = points
+            .windows(2)
+            .map(|p| ChildShape {
+                offset: Vec2::new(0.0, 0.0),
+                rotation: 0.0,
+                shape: Shape::Segment { a: p[0], b: p[1] },
+            })
+            .collect();
+        Shape::Compound { children }
+    }
+
+    // Mass, center of mass and rotational inertia for a density in kg per square World
+    // unit. Segments and chains have no area and so no mass, they're for static bodies.
+    pub fn mass_properties(&self, density: Real) -> MassProperties {
+        let mass = self.area() * density;
+        MassProperties {
+            mass,
+            center: self.centroid(),
+            inertia: mass * self.rotational_inertia(),
+        }
+    }
+
+    // Center of the area relative to the body position. Bodies turn around their
+    // position, so shapes are best made with this at zero, see Shape::compound.
+    pub fn centroid(&self) -> Vec2 {
+        match self {
+            Shape::Circle { .. } | Shape::Rect { .. } | Shape::Capsule { .. } => {
+                Vec2::new(0.0, 0.0)
+            }
+            Shape::Segment { a, b } => (*a + *b) * 0.5,
+            // Sum over the triangles between the body position and each edge.
+            Shape::Polygon { verts } => {
+                let (twice_area, sum) = polygon_triangles(verts)
+                    .fold((0.0, Vec2::new(0.0, 0.0)), |(area, sum), (e1, e2, d)| {
+                        (area + d, sum + (e1 + e2) * (d / 3.0))
+                    });
+                if twice_area == 0.0 {
+                    return Vec2::new(0.0, 0.0);
+                }
+                sum / twice_area
+            }
+            Shape::Compound { children } => Self::compound_centroid(children),
+        }
+    }
+
+    // Per unit of mass, around the body position.
+    pub fn rotational_inertia(&self) -> Real {
         match self {
             Shape::Circle { radius } => 0.5 * radius * radius,
             Shape::Rect { w, h } => 1.0 / 12.0 * (w * w + h * h),
-            _ => 1.0,
+            // A box and two half circles, each half circle's center of mass is 4r / 3pi
+            // past the end of the box.
+            Shape::Capsule { length, radius } => {
+                let (circle_area, box_area) = (PI * radius * radius, 2.0 * radius * length);
+                let h = length * 0.5;
+                let cap = 4.0 * radius / (3.0 * PI);
+                let circle = circle_area * (0.5 * radius * radius + h * h + 2.0 * h * cap);
+                let rect = box_area * (4.0 * radius * radius + length * length) / 12.0;
+                (circle + rect) / (circle_area + box_area)
+            }
+            // A thin rod around its middle, moved to the body position.
+            Shape::Segment { a, b } => {
+                (*b - *a).magnitude2() / 12.0 + ((*a + *b) * 0.5).magnitude2()
+            }
+            // Each triangle between the body position and an edge, integrated over its
+            // area. Signed, so the winding doesn't matter.
+            // https://en.wikipedia.org/wiki/List_of_moments_of_inertia
+            Shape::Polygon { verts } => {
+                let (twice_area, sum) =
+                    polygon_triangles(verts).fold((0.0, 0.0), |(area, sum), (e1, e2, d)| {
+                        let squares = e1.dot(e1) + e1.dot(e2) + e2.dot(e2);
+                        (area + d, sum + d * squares / 6.0)
+                    });
+                if twice_area == 0.0 {
+                    return 0.0;
+                }
+                sum / twice_area
+            }
+            // Parallel axis theorem, each child's mass is its share of the area. A child
+            // turns around its own center of mass, moved to where it is in the body.
+            // https://en.wikipedia.org/wiki/Parallel_axis_theorem
+            Shape::Compound { children } => {
+                let area = self.area();
+                if area <= 0.0 {
+                    return 0.0;
+                }
+                children
+                    .iter()
+                    .map(|c| {
+                        let centroid = c.shape.centroid();
+                        let own = c.shape.rotational_inertia() - centroid.magnitude2();
+                        let moved = (c.offset + centroid.rotate(c.rotation)).magnitude2();
+                        c.shape.area() * (own + moved)
+                    })
+                    .sum::<Real>()
+                    / area
+            }
+        }
+    }
+
+    pub fn area(&self) -> Real {
+        match self {
+            Shape::Circle { radius } => PI * radius * radius,
+            Shape::Rect { w, h } => w * h,
+            Shape::Capsule { length, radius } => PI * radius * radius + 2.0 * radius * length,
+            Shape::Segment { .. } => 0.0,
+            // https://en.wikipedia.org/wiki/Shoelace_formula
+            Shape::Polygon { verts } => {
+                let twice_area: Real = (0..verts.len())
+                    .map(|i| {
+                        let (a, b) = (verts[i], verts[(i + 1) % verts.len()]);
+                        a.x * b.y - a.y * b.x
+                    })
+                    .sum();
+                (twice_area * 0.5).abs()
+            }
+            Shape::Compound { children } => children.iter().map(|c| c.shape.area()).sum(),
+        }
+    }
+
+    // The non-compound shapes making up the shape with their world positions and
+    // rotations, for a body at position and rotation. Just the shape itself unless it's
+    // a compound.
+    pub fn parts_at(&self, position: Vec2, rotation: Real) -> Vec<(&Shape, Vec2, Real)> {
+        match self {
+            Shape::Compound { children } => children
+                .iter()
+                .flat_map(|c| {
+                    c.shape
+                        .parts_at(position + c.offset.rotate(rotation), rotation + c.rotation)
+                })
+                .collect(),
+            _ => vec![(self, position, rotation)],
+        }
+    }
+
+    // Rect and Polygon vertices rotated and moved into world space, the end points for
+    // Segments. Circles, capsules and compounds have no vertices of their own, see
+    // core_at and parts_at.
+    pub fn world_vertices(&self, physics: &Physics) -> Vec<Vec2> {
+        self.vertices_at(physics.position, physics.rotation)
+    }
+
+    // World space bounding box, for the broadphase.
+    pub fn aabb(&self, physics: &Physics) -> Aabb {
+        self.aabb_at(physics.position, physics.rotation)
+    }
+
+    // As world_vertices and aabb, for a body at some other position and rotation.
+    pub fn vertices_at(&self, position: Vec2, rotation: Real) -> Vec<Vec2> {
+        let transform = Transform2::new(position, rotation);
+        match self {
+            Shape::Circle { .. } | Shape::Capsule { .. } | Shape::Compound { .. } => Vec::new(),
+            Shape::Segment { a, b } => vec![transform.apply(*a), transform.apply(*b)],
+            Shape::Rect { w, h } => [
+                Vec2::new(w * -0.5, h * -0.5),
+                Vec2::new(w * 0.5, h * -0.5),
+                Vec2::new(w * 0.5, h * 0.5),
+                Vec2::new(w * -0.5, h * 0.5),
+            ]
+            .iter()
+            .map(|v| transform.apply(*v))
+            .collect(),
+            Shape::Polygon { verts } => verts.iter().map(|v| transform.apply(*v)).collect(),
+        }
+    }
+
+    // Convex shape without the rounding, and the radius it's grown by. Circles are a
+    // single point, capsules a segment.
+    pub(crate) fn core_at(&self, position: Vec2, rotation: Real) -> (Vec<Vec2>, Real) {
+        match self {
+            Shape::Circle { radius } => (vec![position], *radius),
+            Shape::Capsule { length, radius } => {
+                let half = Vec2::new(0.0, length * 0.5).rotate(rotation);
+                (vec![position - half, position + half], *radius)
+            }
+            _ => (self.vertices_at(position, rotation), 0.0),
+        }
+    }
+
+    pub fn aabb_at(&self, position: Vec2, rotation: Real) -> Aabb {
+        match self {
+            Shape::Circle { .. } | Shape::Capsule { .. } => {
+                let (core, radius) = self.core_at(position, rotation);
+                let aabb = Aabb::from_points(&core);
+                Aabb::new(
+                    aabb.min - Vec2::new(radius, radius),
+                    aabb.max + Vec2::new(radius, radius),
+                )
+            }
+            Shape::Compound { .. } => self
+                .parts_at(position, rotation)
+                .iter()
+                .map(|(part, position, rotation)| part.aabb_at(*position, *rotation))
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or_default(),
+            _ => Aabb::from_points(&self.vertices_at(position, rotation)),
+        }
+    }
+
+    pub fn contains_point(&self, physics: &Physics, point: Vec2) -> bool {
+        self.parts_at(physics.position, physics.rotation)
+            .into_iter()
+            .any(|(part, position, rotation)| match part {
+                Shape::Circle { .. } | Shape::Capsule { .. } => {
+                    let (core, radius) = part.core_at(position, rotation);
+                    let closest = closest_on_segment(core[0], *core.last().unwrap(), point);
+                    (point - closest).magnitude2() <= radius * radius
+                }
+                Shape::Segment { .. } => false,
+                _ => {
+                    let verts = part.vertices_at(position, rotation);
+                    Collision::max_separation(&verts, &[point]).0 <= 0.0
+                }
+            })
+    }
+
+    // Distance along the ray to where it enters the shape, and the surface normal there.
+    // direction is a unit vector. Rays starting inside the shape don't hit it.
+    pub fn raycast(
+        &self,
+        physics: &Physics,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: Real,
+    ) -> Option<(Real, Vec2)> {
+        if matches!(self, Shape::Compound { .. } | Shape::Capsule { .. })
+            && self.contains_point(physics, origin)
+        {
+            return None;
+        }
+        self.parts_at(physics.position, physics.rotation)
+            .into_iter()
+            .filter_map(|(part, position, rotation)| {
+                part.raycast_part(position, rotation, origin, direction, max_distance)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    fn raycast_part(
+        &self,
+        position: Vec2,
+        rotation: Real,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: Real,
+    ) -> Option<(Real, Vec2)> {
+        match self {
+            Shape::Circle { radius } => {
+                let m = origin - position;
+                let b = m.dot(direction);
+                let c = m.magnitude2() - radius * radius;
+                let discriminant = b * b - c;
+                if c <= 0.0 || b > 0.0 || discriminant < 0.0 {
+                    return None;
+                }
+                let distance = -b - discriminant.sqrt();
+                let normal = (origin + direction * distance - position).unit();
+                (distance <= max_distance).then_some((distance, normal))
+            }
+            // The box between the two end circles, and the circles.
+            Shape::Capsule { length, radius } => {
+                let (core, radius) = (self.core_at(position, rotation).0, *radius);
+                let middle = Shape::Rect {
+                    w: radius * 2.0,
+                    h: *length,
+                };
+                let end = Shape::Circle { radius };
+                [
+                    middle.raycast_part(position, rotation, origin, direction, max_distance),
+                    end.raycast_part(core[0], 0.0, origin, direction, max_distance),
+                    end.raycast_part(core[1], 0.0, origin, direction, max_distance),
+                ]
+                .into_iter()
+                .flatten()
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+            }
+            // Where the ray crosses the line, if that's between the end points. Hit from
+            // either side, the normal faces the ray.
+            Shape::Segment { .. } => {
+                let verts = self.vertices_at(position, rotation);
+                let edge = verts[1] - verts[0];
+                let denominator = direction.cross(edge);
+                if denominator == 0.0 {
+                    return None;
+                }
+                let to_start = verts[0] - origin;
+                let distance = to_start.cross(edge) / denominator;
+                let along = to_start.cross(direction) / denominator;
+                if distance < 0.0 || distance > max_distance || !(0.0..=1.0).contains(&along) {
+                    return None;
+                }
+                let normal = edge.normal_positive().unit();
+                let normal = if normal.dot(direction) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                };
+                Some((distance, normal))
+            }
+            // Clips the ray against the half plane behind each edge.
+            // https://en.wikipedia.org/wiki/Cyrus%E2%80%93Beck_algorithm
+            _ => {
+                let verts = self.vertices_at(position, rotation);
+                let (mut enter, mut exit) = (0.0, max_distance);
+                let mut normal = None;
+                for i in 0..verts.len() {
+                    let v0 = verts[i];
+                    let edge_normal = (verts[(i + 1) % verts.len()] - v0).normal_positive().unit();
+                    let offset = (v0 - origin).dot(edge_normal);
+                    let approach = direction.dot(edge_normal);
+                    if approach == 0.0 {
+                        if offset < 0.0 {
+                            return None; // Parallel and outside.
+                        }
+                    } else if approach < 0.0 {
+                        let t = offset / approach;
+                        if t > enter {
+                            enter = t;
+                            normal = Some(edge_normal);
+                        }
+                    } else {
+                        exit = Real::min(exit, offset / approach);
+                    }
+                    if exit < enter {
+                        return None;
+                    }
+                }
+                normal.map(|normal| (enter, normal))
+            }
         }
     }
+
+    // Distance from the body position to the farthest point of the shape.
+    pub fn bounding_radius(&self) -> Real {
+        match self {
+            Shape::Circle { radius } => *radius,
+            Shape::Capsule { length, radius } => length * 0.5 + radius,
+            Shape::Compound { children } => children
+                .iter()
+                .map(|c| c.offset.magnitude() + c.shape.bounding_radius())
+                .fold(0.0, Real::max),
+            _ => self
+                .vertices_at(Vec2::new(0.0, 0.0), 0.0)
+                .iter()
+                .map(|v| v.magnitude())
+                .fold(0.0, Real::max),
+        }
+    }
+}
+
+// Points closer than this to the rest of the outline add nothing to it.
+fn outline_tolerance(points: &[Vec2]) -> Real {
+    let aabb = Aabb::from_points(points);
+    (aabb.max - aabb.min).magnitude() * 0.0001
+}
+
+// The two edges from the body position to each polygon edge, and twice the triangle's
+// signed area.
+fn polygon_triangles(verts: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2, Real)> + '_ {
+    (0..verts.len()).map(|i| {
+        let (e1, e2) = (verts[i], verts[(i + 1) % verts.len()]);
+        (e1, e2, e1.cross(e2))
+    })
+}
+
+// Point of the segment from a to b closest to p.
+pub(crate) fn closest_on_segment(a: Vec2, b: Vec2, p: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    if length2 <= Real::EPSILON {
+        return a;
+    }
+    a + ab * ((p - a).dot(ab) / length2).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compound_mass_properties() {
+        let square = |x: Real| ChildShape {
+            offset: Vec2::new(x, 1.0),
+            rotation: 0.0,
+            shape: Shape::Rect { w: 1.0, h: 1.0 },
+        };
+        let (shape, center) = Shape::compound(vec![square(0.0), square(1.0)]);
+        assert!(center.nearly_eq(Vec2::new(0.5, 1.0)));
+
+        // Same as one 2 by 1 rectangle.
+        let rect = Shape::Rect { w: 2.0, h: 1.0 };
+        assert!((shape.area() - 2.0).abs() < 0.0001);
+        assert!((shape.rotational_inertia() - rect.rotational_inertia()).abs() < 0.0001);
+        let aabb = shape.aabb_at(Vec2::new(0.0, 0.0), crate::vec2::consts::FRAC_PI_2);
+        assert!(aabb.min.nearly_eq(Vec2::new(-0.5, -1.0)));
+        assert!(aabb.max.nearly_eq(Vec2::new(0.5, 1.0)));
+        // Never less than the real one.
+        assert!(shape.bounding_radius() >= rect.bounding_radius());
+    }
+
+    #[test]
+    fn capsule_mass_properties() {
+        // Without a middle it's a circle, and it's never lighter to turn than the box
+        // around its middle.
+        let circle = Shape::Circle { radius: 0.5 };
+        let round = Shape::Capsule {
+            length: 0.0,
+            radius: 0.5,
+        };
+        assert!((round.area() - circle.area()).abs() < 0.0001);
+        assert!((round.rotational_inertia() - circle.rotational_inertia()).abs() < 0.0001);
+        let capsule = Shape::Capsule {
+            length: 2.0,
+            radius: 0.5,
+        };
+        let middle = Shape::Rect { w: 1.0, h: 2.0 };
+        assert!(capsule.rotational_inertia() > middle.rotational_inertia());
+        assert_eq!(capsule.bounding_radius(), 1.5);
+
+        let chain = Shape::chain(&[
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(2.0, 0.0),
+        ]);
+        let aabb = chain.aabb_at(Vec2::new(0.0, 0.0), 0.0);
+        assert!(aabb.min.nearly_eq(Vec2::new(0.0, -1.0)));
+        assert!(aabb.max.nearly_eq(Vec2::new(2.0, 0.0)));
+    }
+
+    #[test]
+    fn polygon_mass_properties() {
+        // The same box as a polygon and a rect.
+        let rect = Shape::Rect { w: 2.0, h: 1.0 };
+        let square = Shape::Polygon {
+            verts: rect.vertices_at(Vec2::new(0.0, 0.0), 0.0),
+        };
+        assert!((square.rotational_inertia() - rect.rotational_inertia()).abs() < 0.0001);
+        assert_eq!(
+            square.mass_properties(3.0),
+            MassProperties {
+                mass: 6.0,
+                center: Vec2::new(0.0, 0.0),
+                inertia: 6.0 * rect.rotational_inertia(),
+            }
+        );
+
+        // Right triangle, the center is a third of the way in from the corner.
+        let triangle = Shape::Polygon {
+            verts: vec![
+                Vec2::new(0.0, -3.0),
+                Vec2::new(3.0, 0.0),
+                Vec2::new(0.0, 0.0),
+            ],
+        };
+        assert!(triangle.centroid().nearly_eq(Vec2::new(1.0, -1.0)));
+        // Around the center it's (a^2 + b^2) / 18 per unit mass.
+        let around_center = triangle.rotational_inertia() - triangle.centroid().magnitude2();
+        assert!((around_center - 18.0 / 18.0).abs() < 0.0001);
+
+        // A compound puts the triangle's center at the body position.
+        let (compound, center) = Shape::compound(vec![ChildShape {
+            offset: Vec2::new(0.0, 0.0),
+            rotation: 0.0,
+            shape: triangle,
+        }]);
+        assert!(center.nearly_eq(Vec2::new(1.0, -1.0)));
+        assert!(compound.centroid().nearly_eq(Vec2::new(0.0, 0.0)));
+        assert!((compound.rotational_inertia() - around_center).abs() < 0.0001);
+
+        let circle = Shape::Circle { radius: 2.0 };
+        let physics = Physics::from_shape(Vec2::new(0.0, 0.0), &circle, 0.5, 0.0).unwrap();
+        assert!((physics.mass - 2.0 * PI).abs() < 0.0001);
+        assert!((physics.angular_mass - 2.0 * PI * 2.0).abs() < 0.0001);
+
+        // Nothing to weigh, the inverse masses would blow up.
+        let segment = Shape::Segment {
+            a: Vec2::new(0.0, 0.0),
+            b: Vec2::new(1.0, 0.0),
+        };
+        assert!(Physics::from_shape(Vec2::new(0.0, 0.0), &segment, 0.5, 0.0).is_none());
+        assert!(Physics::from_shape(Vec2::new(0.0, 0.0), &circle, 0.0, 0.0).is_none());
+        assert!(Physics::from_shape(Vec2::new(0.0, 0.0), &circle, -1.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn validates_polygons() {
+        let backwards = [
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(-1.0, -1.0),
+        ];
+        let Some(Shape::Polygon { verts }) = Shape::polygon(&backwards) else {
+            panic!("not a polygon");
+        };
+        assert_eq!(verts.len(), 4);
+        assert_eq!(geometry::signed_area(&verts), 4.0);
+        assert!(Shape::polygon(&backwards[..2]).is_none());
+
+        let arrow = [
+            Vec2::new(0.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(-1.0, 1.0),
+        ];
+        assert!(Shape::polygon(&arrow).is_none());
+        let shape = Shape::decomposed(&arrow).unwrap();
+        assert!((shape.area() - 1.0).abs() < 0.0001);
+        let physics = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        assert!(shape.contains_point(&physics, Vec2::new(0.5, 0.5)));
+        assert!(!shape.contains_point(&physics, Vec2::new(0.0, 0.5)));
+
+        // A bow tie crosses itself, one of its halves is wound the wrong way.
+        let bow_tie = [
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(-1.0, 1.0),
+        ];
+        assert!(Shape::decomposed(&bow_tie).is_none());
+    }
 }
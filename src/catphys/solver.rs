@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+
+use crate::manifold::Manifold;
+use crate::physics::Physics;
+use crate::vec2::{Real, Rot2, Vec2};
+
+// Sequential impulses, as in Erin Catto's Box2D Lite.
+// https://box2d.org/files/ErinCatto_SequentialImpulses_GDC2006.pdf
+//
+// Contacts for the whole step are gathered first with add_contact, then solved together:
+// solve_velocities runs between Physics::integrate_velocity and integrate_position, and
+// solve_positions pushes apart whatever penetration is left after integrate_position.
+pub struct ContactSolver {
+    pub velocity_iterations: usize,
+    pub position_iterations: usize,
+    pub baumgarte: Real, // Fraction of the penetration removed per iteration.
+    pub slop: Real,      // Penetration allowed to keep contacts alive.
+    pub max_correction: Real, // Largest position correction in one iteration.
+    pub restitution_threshold: Real, // Slower hits than this don't bounce.
+    pub warm_starting: bool,
+
+    contacts: Vec<Contact>,
+    // Accumulated impulses from the previous step, by body pair and contact point part
+    // and id.
+    cache: HashMap<(usize, usize), Vec<CachedImpulse>>,
+}
+
+// Contact point part and id, normal impulse, tangent impulse.
+type CachedImpulse = (u32, u32, Real, Real);
+
+struct Contact {
+    a: usize,
+    b: usize,
+    manifold: Manifold,
+    points: Vec<PointConstraint>,
+    local_normal: Vec2, // Normal in body a space.
+    static_friction: Real,
+    dynamic_friction: Real,
+}
+
+#[derive(Default)]
+struct PointConstraint {
+    part: u32,
+    id: u32,
+    ra: Vec2, // From the body centers to the contact point.
+    rb: Vec2,
+    // Surface points in body space, for measuring the remaining penetration later.
+    local_a: Vec2,
+    local_b: Vec2,
+    normal_mass: Real,
+    tangent_mass: Real,
+    velocity_bias: Real,
+    normal_impulse: Real,
+    tangent_impulse: Real,
+}
+
+impl Default for ContactSolver {
+    fn default() -> Self {
+        Self {
+            velocity_iterations: 20,
+            position_iterations: 3,
+            baumgarte: 0.2,
+            slop: 0.005,
+            max_correction: 0.2,
+            restitution_threshold: 1.0,
+            warm_starting: true,
+            contacts: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl ContactSolver {
+    // Body indices refer to the slice later passed to solve_velocities.
+    pub fn add_contact(&mut self, a: usize, b: usize, manifold: &Manifold) {
+        self.contacts.push(Contact {
+            a,
+            b,
+            manifold: *manifold,
+            points: Vec::new(),
+            local_normal: Vec2::default(),
+            static_friction: 0.0,
+            dynamic_friction: 0.0,
+        });
+    }
+
+    // For worlds that swap remove: body `index` is gone and body `last` was moved into its
+    // place. Keeps the warm starting cache pointing at the right bodies. Pairs come from
+    // the broadphase smaller index first, so renamed ones are put back in that order.
+    pub fn remove_body(&mut self, index: usize, last: usize) {
+        let rename = |i: usize| if i == last { index } else { i };
+        self.cache = std::mem::take(&mut self.cache)
+            .into_iter()
+            .filter(|((a, b), _)| *a != index && *b != index)
+            .map(|((a, b), impulses)| {
+                let (a, b) = (rename(a), rename(b));
+                ((a.min(b), a.max(b)), impulses)
+            })
+            .collect();
+    }
+
+    pub fn solve_velocities(&mut self, bodies: &mut [Physics]) {
+        self.begin_velocities(bodies);
+        for _ in 0..self.velocity_iterations {
+            self.iterate_velocities(bodies);
+        }
+        self.end_velocities();
+    }
+
+    // solve_velocities in parts, so other constraints like joints can be solved in the
+    // same iterations: begin once, iterate velocity_iterations times, then end.
+    pub fn begin_velocities(&mut self, bodies: &mut [Physics]) {
+        self.prepare(bodies);
+
+        if self.warm_starting {
+            for contact in &self.contacts {
+                let normal = contact.manifold.normal();
+                let tangent = normal.normal_positive();
+                for point in &contact.points {
+                    let impulse = normal * point.normal_impulse + tangent * point.tangent_impulse;
+                    apply(bodies, contact.a, contact.b, point, impulse);
+                }
+            }
+        }
+    }
+
+    pub fn iterate_velocities(&mut self, bodies: &mut [Physics]) {
+        for contact in &mut self.contacts {
+            Self::solve_contact(contact, bodies);
+        }
+    }
+
+    // Compound shapes can give a pair of bodies several contacts, their points share the
+    // cache entry.
+    pub fn end_velocities(&mut self) {
+        self.cache.clear();
+        for c in &self.contacts {
+            self.cache.entry((c.a, c.b)).or_default().extend(
+                c.points
+                    .iter()
+                    .map(|p| (p.part, p.id, p.normal_impulse, p.tangent_impulse)),
+            );
+        }
+    }
+
+    // Normal and tangent impulses applied to each contact during the step, summed over the
+    // contact points, in the order the contacts were added. Valid until solve_positions.
+    pub fn contact_impulses(&self) -> impl Iterator<Item = (Real, Real)> + '_ {
+        self.contacts.iter().map(|c| {
+            c.points.iter().fold((0.0, 0.0), |(normal, tangent), p| {
+                (normal + p.normal_impulse, tangent + p.tangent_impulse)
+            })
+        })
+    }
+
+    // Pushes apart whatever penetration is left after the positions moved, a fraction at a
+    // time like Baumgarte stabilization but without feeding it back into the velocities.
+    // The normal turns along with body a, so a leaning body gets straightened too.
+    // Clears the contacts, so the next step starts gathering from scratch.
+    pub fn solve_positions(&mut self, bodies: &mut [Physics]) {
+        for _ in 0..self.position_iterations {
+            for contact in &self.contacts {
+                for point in &contact.points {
+                    let (pa, pb) = (&bodies[contact.a], &bodies[contact.b]);
+                    let (ta, tb) = (pa.transform(), pb.transform());
+                    let normal = ta.apply_vector(contact.local_normal);
+                    let surface_a = ta.apply(point.local_a);
+                    let surface_b = tb.apply(point.local_b);
+                    let separation = (surface_b - surface_a).dot(normal);
+
+                    let correction = Real::clamp(
+                        self.baumgarte * (separation + self.slop),
+                        -self.max_correction,
+                        0.0,
+                    );
+                    let contact_point = (surface_a + surface_b) * 0.5;
+                    let ra = contact_point - pa.position;
+                    let rb = contact_point - pb.position;
+                    let mass = inverse_or_zero(effective_mass(pa, pb, ra, rb, normal));
+
+                    let impulse = normal * (-correction * mass);
+                    let pa = &mut bodies[contact.a];
+                    pa.position -= impulse * pa.inverse_mass;
+                    pa.rotation -= ra.cross(impulse) * pa.inverse_angular_mass;
+                    let pb = &mut bodies[contact.b];
+                    pb.position += impulse * pb.inverse_mass;
+                    pb.rotation += rb.cross(impulse) * pb.inverse_angular_mass;
+                }
+            }
+        }
+
+        self.contacts.clear();
+    }
+
+    fn prepare(&mut self, bodies: &[Physics]) {
+        for contact in &mut self.contacts {
+            let (pa, pb) = (&bodies[contact.a], &bodies[contact.b]);
+            let normal = contact.manifold.normal();
+            let tangent = normal.normal_positive();
+            let restitution = Real::min(pa.restitution, pb.restitution);
+            contact.static_friction = Real::sqrt(pa.static_friction * pb.static_friction);
+            contact.dynamic_friction = Real::sqrt(pa.dynamic_friction * pb.dynamic_friction);
+
+            let (rot_a, rot_b) = (Rot2::new(pa.rotation), Rot2::new(pb.rotation));
+            contact.local_normal = rot_a.inverse_rotate(normal);
+            let cached = self.cache.get(&(contact.a, contact.b));
+
+            contact.points = contact
+                .manifold
+                .points()
+                .iter()
+                .map(|point| {
+                    let ra = point.position - pa.position;
+                    let rb = point.position - pb.position;
+                    let half_depth = normal * (point.depth * 0.5);
+
+                    let mut constraint = PointConstraint {
+                        part: point.part,
+                        id: point.id,
+                        ra,
+                        rb,
+                        local_a: rot_a.inverse_rotate(ra + half_depth),
+                        local_b: rot_b.inverse_rotate(rb - half_depth),
+                        normal_mass: inverse_or_zero(effective_mass(pa, pb, ra, rb, normal)),
+                        tangent_mass: inverse_or_zero(effective_mass(pa, pb, ra, rb, tangent)),
+                        ..Default::default()
+                    };
+
+                    let normal_velocity = (pb.velocity_at(point.position)
+                        - pa.velocity_at(point.position))
+                    .dot(normal);
+                    if normal_velocity < -self.restitution_threshold {
+                        constraint.velocity_bias = -restitution * normal_velocity;
+                    }
+
+                    if let Some(&(_, _, normal_impulse, tangent_impulse)) = cached.and_then(|c| {
+                        c.iter()
+                            .find(|(part, id, _, _)| *part == point.part && *id == point.id)
+                    }) {
+                        constraint.normal_impulse = normal_impulse;
+                        constraint.tangent_impulse = tangent_impulse;
+                    }
+
+                    constraint
+                })
+                .collect();
+        }
+    }
+
+    fn solve_contact(contact: &mut Contact, bodies: &mut [Physics]) {
+        let normal = contact.manifold.normal();
+        let tangent = normal.normal_positive();
+
+        // Friction first, it's limited by the normal impulse so it's the less important one.
+        for point in &mut contact.points {
+            let (pa, pb) = (&bodies[contact.a], &bodies[contact.b]);
+            let relative_velocity = pb.velocity + point_velocity(pb, point.rb)
+                - pa.velocity
+                - point_velocity(pa, point.ra);
+
+            let lambda = -point.tangent_mass * relative_velocity.dot(tangent);
+            let max_static = contact.static_friction * point.normal_impulse;
+            let mut total = point.tangent_impulse + lambda;
+            if total.abs() > max_static {
+                // Sliding.
+                total = total.signum() * contact.dynamic_friction * point.normal_impulse;
+            }
+            let lambda = total - point.tangent_impulse;
+            point.tangent_impulse = total;
+
+            apply(bodies, contact.a, contact.b, point, tangent * lambda);
+        }
+
+        if contact.points.len() == 2 && Self::solve_block(contact, bodies) {
+            return;
+        }
+
+        for point in &mut contact.points {
+            let (pa, pb) = (&bodies[contact.a], &bodies[contact.b]);
+            let relative_velocity = pb.velocity + point_velocity(pb, point.rb)
+                - pa.velocity
+                - point_velocity(pa, point.ra);
+
+            let lambda = -point.normal_mass * (relative_velocity.dot(normal) - point.velocity_bias);
+            // Contacts can only push, so the accumulated impulse stays positive.
+            let total = Real::max(point.normal_impulse + lambda, 0.0);
+            let lambda = total - point.normal_impulse;
+            point.normal_impulse = total;
+
+            apply(bodies, contact.a, contact.b, point, normal * lambda);
+        }
+    }
+
+    // Solving two points one after the other makes a resting box rock from side to side,
+    // stacks need both normal impulses found together. This is a 2x2 linear
+    // complementarity problem, small enough to try each case of which points push.
+    // https://github.com/erincatto/box2d/blob/v2.4.1/src/dynamics/b2_contact_solver.cpp
+    // Returns false when the points are too close together for the block to be solvable.
+    fn solve_block(contact: &mut Contact, bodies: &mut [Physics]) -> bool {
+        let normal = contact.manifold.normal();
+        let (pa, pb) = (&bodies[contact.a], &bodies[contact.b]);
+        let (p1, p2) = (&contact.points[0], &contact.points[1]);
+
+        let rn1a = p1.ra.cross(normal);
+        let rn1b = p1.rb.cross(normal);
+        let rn2a = p2.ra.cross(normal);
+        let rn2b = p2.rb.cross(normal);
+        let inverse_mass = pa.inverse_mass + pb.inverse_mass;
+        let k11 = inverse_mass
+            + pa.inverse_angular_mass * rn1a * rn1a
+            + pb.inverse_angular_mass * rn1b * rn1b;
+        let k22 = inverse_mass
+            + pa.inverse_angular_mass * rn2a * rn2a
+            + pb.inverse_angular_mass * rn2b * rn2b;
+        let k12 = inverse_mass
+            + pa.inverse_angular_mass * rn1a * rn2a
+            + pb.inverse_angular_mass * rn1b * rn2b;
+        let determinant = k11 * k22 - k12 * k12;
+        if k11 * k11 >= 1000.0 * determinant {
+            return false;
+        }
+
+        let relative_velocity = |p: &PointConstraint| {
+            (pb.velocity + point_velocity(pb, p.rb) - pa.velocity - point_velocity(pa, p.ra))
+                .dot(normal)
+        };
+        let old = (p1.normal_impulse, p2.normal_impulse);
+        // Velocities the impulses have to cancel, as if the old impulses weren't applied.
+        let b1 = relative_velocity(p1) - p1.velocity_bias - (k11 * old.0 + k12 * old.1);
+        let b2 = relative_velocity(p2) - p2.velocity_bias - (k12 * old.0 + k22 * old.1);
+
+        let both = (
+            -(k22 * b1 - k12 * b2) / determinant,
+            -(k11 * b2 - k12 * b1) / determinant,
+        );
+        let first = (-b1 / k11, 0.0);
+        let second = (0.0, -b2 / k22);
+
+        let impulses = if both.0 >= 0.0 && both.1 >= 0.0 {
+            both
+        } else if first.0 >= 0.0 && k12 * first.0 + b2 >= 0.0 {
+            first
+        } else if second.1 >= 0.0 && k12 * second.1 + b1 >= 0.0 {
+            second
+        } else if b1 >= 0.0 && b2 >= 0.0 {
+            (0.0, 0.0)
+        } else {
+            return false;
+        };
+
+        contact.points[0].normal_impulse = impulses.0;
+        contact.points[1].normal_impulse = impulses.1;
+        apply(
+            bodies,
+            contact.a,
+            contact.b,
+            &contact.points[0],
+            normal * (impulses.0 - old.0),
+        );
+        apply(
+            bodies,
+            contact.a,
+            contact.b,
+            &contact.points[1],
+            normal * (impulses.1 - old.1),
+        );
+
+        true
+    }
+}
+
+// Impulse pushes b along it and a the opposite way.
+fn apply(bodies: &mut [Physics], a: usize, b: usize, point: &PointConstraint, impulse: Vec2) {
+    let pa = &mut bodies[a];
+    pa.velocity -= impulse * pa.inverse_mass;
+    pa.angular_velocity -= point.ra.cross(impulse) * pa.inverse_angular_mass;
+    let pb = &mut bodies[b];
+    pb.velocity += impulse * pb.inverse_mass;
+    pb.angular_velocity += point.rb.cross(impulse) * pb.inverse_angular_mass;
+}
+
+// Velocity of a point at arm r due to rotation only.
+fn point_velocity(physics: &Physics, r: Vec2) -> Vec2 {
+    Vec2::scalar_cross(physics.angular_velocity, r)
+}
+
+fn effective_mass(pa: &Physics, pb: &Physics, ra: Vec2, rb: Vec2, direction: Vec2) -> Real {
+    let ra_cross = ra.cross(direction);
+    let rb_cross = rb.cross(direction);
+    pa.inverse_mass
+        + pb.inverse_mass
+        + ra_cross * ra_cross * pa.inverse_angular_mass
+        + rb_cross * rb_cross * pb.inverse_angular_mass
+}
+
+pub(crate) fn inverse_or_zero(value: Real) -> Real {
+    if value > 0.0 {
+        1.0 / value
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContactSolver;
+    use crate::collision::Collision;
+    use crate::physics::Physics;
+    use crate::shape::Shape;
+    use crate::vec2::{Real, Vec2};
+
+    #[test]
+    fn stacks_twenty_boxes() {
+        let dt = 1.0 / 60.0;
+        let gravity = Vec2::new(0.0, 9.81);
+        let ground_shape = Shape::Rect { w: 20.0, h: 1.0 };
+        let box_shape = Shape::Rect { w: 1.0, h: 1.0 };
+
+        let mut shapes = vec![&ground_shape];
+        let mut bodies = vec![Physics::new_static(Vec2::new(0.0, 0.5), 0.0)];
+        for i in 0..20 {
+            shapes.push(&box_shape);
+            bodies.push(Physics::new(
+                Vec2::new(if i % 2 == 0 { 0.02 } else { -0.02 }, -0.5 - i as Real),
+                1.0,
+                box_shape.rotational_inertia(),
+                0.0,
+            ));
+        }
+
+        let mut solver = ContactSolver::default();
+        for _ in 0..600 {
+            for body in bodies.iter_mut().skip(1) {
+                body.apply_force(gravity * body.mass);
+                body.integrate_velocity(dt);
+            }
+            for i in 0..bodies.len() - 1 {
+                for j in i + 1..bodies.len() {
+                    if let Some(c) = Collision::detect(shapes[i], shapes[j], &bodies[i], &bodies[j])
+                    {
+                        solver.add_contact(i, j, c.manifold());
+                    }
+                }
+            }
+            solver.solve_velocities(&mut bodies);
+            bodies.iter_mut().for_each(|b| b.integrate_position(dt));
+            solver.solve_positions(&mut bodies);
+        }
+
+        // Every other box is shifted a little, so the stack isn't perfectly symmetric.
+        let top = &bodies[20];
+        assert!((top.position.x - -0.02).abs() < 0.1);
+        assert!((top.position.y - -19.5).abs() < 0.1);
+        for body in bodies.iter().skip(1) {
+            assert!(body.velocity.magnitude() < 0.05);
+            assert!(body.rotation.abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn removing_a_body_keeps_the_cache() {
+        let dt = 1.0 / 60.0;
+        let ground_shape = Shape::Rect { w: 20.0, h: 1.0 };
+        let box_shape = Shape::Rect { w: 1.0, h: 1.0 };
+
+        // A lone box at index 1 and a stack of three after it.
+        let mut shapes = vec![&ground_shape];
+        let mut bodies = vec![Physics::new_static(Vec2::new(0.0, 0.5), 0.0)];
+        for position in [(8.0, -0.5), (0.0, -0.5), (0.0, -1.5), (0.0, -2.5)] {
+            shapes.push(&box_shape);
+            bodies.push(Physics::new(
+                Vec2::new(position.0, position.1),
+                1.0,
+                box_shape.rotational_inertia(),
+                0.0,
+            ));
+        }
+
+        let mut solver = ContactSolver::default();
+        let step = |bodies: &mut Vec<Physics>, shapes: &[&Shape], solver: &mut ContactSolver| {
+            for body in bodies.iter_mut().skip(1) {
+                body.apply_force(Vec2::new(0.0, 9.81) * body.mass);
+                body.integrate_velocity(dt);
+            }
+            let mut pairs = Vec::new();
+            for i in 0..bodies.len() - 1 {
+                for j in i + 1..bodies.len() {
+                    if let Some(c) = Collision::detect(shapes[i], shapes[j], &bodies[i], &bodies[j])
+                    {
+                        solver.add_contact(i, j, c.manifold());
+                        pairs.push((i, j));
+                    }
+                }
+            }
+            solver.solve_velocities(bodies);
+            bodies.iter_mut().for_each(|b| b.integrate_position(dt));
+            solver.solve_positions(bodies);
+            pairs
+        };
+        for _ in 0..120 {
+            step(&mut bodies, &shapes, &mut solver);
+        }
+
+        // The top box moves down to index 1, its contact with the box below is now (1, 3).
+        bodies.swap_remove(1);
+        shapes.swap_remove(1);
+        solver.remove_body(1, bodies.len());
+        let cached: Vec<(usize, usize)> = solver.cache.keys().copied().collect();
+        let pairs = step(&mut bodies, &shapes, &mut solver);
+        assert!(pairs.contains(&(1, 3)));
+        for pair in pairs {
+            assert!(cached.contains(&pair));
+        }
+        assert!(bodies.iter().all(|b| b.velocity.magnitude() < 0.05));
+    }
+}
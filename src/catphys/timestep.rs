@@ -0,0 +1,72 @@
+use crate::vec2::Real;
+
+// Turns variable frame times into a whole number of fixed size steps, so the simulation
+// behaves the same regardless of the frame rate.
+// https://gafferongames.com/post/fix_your_timestep/
+pub struct FixedTimestep {
+    dt: Real,
+    max_steps: u32, // Per frame, the rest of a long frame is dropped.
+    accumulator: Real,
+}
+
+impl FixedTimestep {
+    pub fn new(hz: Real, max_steps: u32) -> Self {
+        Self {
+            dt: 1.0 / hz,
+            max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn dt(&self) -> Real {
+        self.dt
+    }
+
+    // Adds the frame time and returns how many steps of dt to run. After a stall only
+    // max_steps are run and the simulation falls behind instead of spiralling.
+    pub fn advance(&mut self, frame_time: Real) -> u32 {
+        self.accumulator += frame_time.max(0.0);
+        let steps = (self.accumulator / self.dt) as u32;
+        if steps > self.max_steps {
+            self.accumulator %= self.dt;
+            self.max_steps
+        } else {
+            self.accumulator -= steps as Real * self.dt;
+            steps
+        }
+    }
+
+    // How far between the last two steps the current frame is, 0 to 1. For blending
+    // Physics::previous_position and position when rendering.
+    pub fn alpha(&self) -> Real {
+        (self.accumulator / self.dt).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60.0, 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_partial_steps() {
+        let mut timestep = FixedTimestep::new(100.0, 8);
+        assert_eq!(timestep.advance(0.025), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(0.005), 1);
+        assert!(timestep.alpha() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(100.0, 4);
+        assert_eq!(timestep.advance(2.0), 4);
+        assert!(timestep.alpha() < 1.0);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::collision::Collision;
+use crate::shape::Shape;
+use crate::vec2::{Real, Vec2};
+
+const MAX_ITERATIONS: usize = 30;
+
+// Motion of a body over one step, positions and rotations in between are interpolated.
+#[derive(Copy, Clone, Debug)]
+pub struct Sweep {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub start_rotation: Real,
+    pub end_rotation: Real,
+}
+
+impl Sweep {
+    // A body that doesn't move.
+    pub fn fixed(position: Vec2, rotation: Real) -> Self {
+        Self {
+            start: position,
+            end: position,
+            start_rotation: rotation,
+            end_rotation: rotation,
+        }
+    }
+
+    pub fn at(&self, t: Real) -> (Vec2, Real) {
+        (
+            self.start + (self.end - self.start) * t,
+            self.start_rotation + (self.end_rotation - self.start_rotation) * t,
+        )
+    }
+}
+
+// Fraction of the step, 0 to 1, at which the two moving shapes first overlap by between
+// depth / 2 and depth. Overlapping a little leaves a contact for the solver to find in
+// the next step. 0 if they already overlap that much at the start, None if they never do.
+pub fn time_of_impact(
+    sa: &Shape,
+    sweep_a: Sweep,
+    sb: &Shape,
+    sweep_b: Sweep,
+    depth: Real,
+) -> Option<Real> {
+    match (sa, sb) {
+        (Shape::Circle { radius: ra }, Shape::Circle { radius: rb }) => {
+            swept_circles(ra + rb, sweep_a, sweep_b, depth)
+        }
+        _ => conservative_advancement(sa, sweep_a, sb, sweep_b, depth),
+    }
+}
+
+// Solves |d + v t| = r for the distance d between the centers, moving at v.
+fn swept_circles(radius: Real, sweep_a: Sweep, sweep_b: Sweep, depth: Real) -> Option<Real> {
+    let d = sweep_b.start - sweep_a.start;
+    let v = (sweep_b.end - sweep_b.start) - (sweep_a.end - sweep_a.start);
+    let r = radius - depth * 0.75;
+    if d.magnitude() <= radius - depth * 0.5 {
+        return Some(0.0);
+    }
+
+    let a = v.magnitude2();
+    let b = 2.0 * d.dot(v);
+    let c = d.magnitude2() - r * r;
+    let discriminant = b * b - 4.0 * a * c;
+    if a <= Real::EPSILON || discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+// Moves both shapes forward by as much as is safe: the separation can shrink no faster
+// than the relative speed plus the rotation speed times the shape size.
+// https://box2d.org/files/ErinCatto_ContinuousCollision_GDC2013.pdf
+fn conservative_advancement(
+    sa: &Shape,
+    sweep_a: Sweep,
+    sb: &Shape,
+    sweep_b: Sweep,
+    depth: Real,
+) -> Option<Real> {
+    let turn = |sweep: Sweep| (sweep.end_rotation - sweep.start_rotation).abs();
+    let max_approach = ((sweep_b.end - sweep_b.start) - (sweep_a.end - sweep_a.start)).magnitude()
+        + turn(sweep_a) * rotating_radius(sa)
+        + turn(sweep_b) * rotating_radius(sb);
+
+    let mut t = 0.0;
+    for _ in 0..MAX_ITERATIONS {
+        let (pa, ra) = sweep_a.at(t);
+        let (pb, rb) = sweep_b.at(t);
+        let distance = separation(sa, pa, ra, sb, pb, rb);
+        if distance <= -depth * 0.5 {
+            return Some(t);
+        }
+        if max_approach <= Real::EPSILON {
+            return None;
+        }
+
+        t += (distance + depth) / max_approach;
+        if t > 1.0 {
+            return None;
+        }
+    }
+    // Still short of the contact, but never past it.
+    Some(t)
+}
+
+// Circles look the same at every rotation.
+fn rotating_radius(shape: &Shape) -> Real {
+    match shape {
+        Shape::Circle { .. } => 0.0,
+        _ => shape.bounding_radius(),
+    }
+}
+
+// Largest gap along a separating axis, never more than the real distance between the
+// shapes and minus the penetration depth when they overlap.
+fn separation(sa: &Shape, pa: Vec2, ra: Real, sb: &Shape, pb: Vec2, rb: Real) -> Real {
+    match (sa, sb) {
+        // The closest pair of parts.
+        (Shape::Compound { .. }, _) | (_, Shape::Compound { .. }) => {
+            let parts_b = sb.parts_at(pb, rb);
+            sa.parts_at(pa, ra)
+                .iter()
+                .flat_map(|(sa, pa, ra)| {
+                    parts_b
+                        .iter()
+                        .map(move |(sb, pb, rb)| separation(sa, *pa, *ra, sb, *pb, *rb))
+                })
+                .fold(Real::MAX, Real::min)
+        }
+        (Shape::Capsule { .. } | Shape::Segment { .. }, _)
+        | (_, Shape::Capsule { .. } | Shape::Segment { .. }) => {
+            let ((va, ra), (vb, rb)) = (sa.core_at(pa, ra), sb.core_at(pb, rb));
+            Collision::rounded_separation(&va, ra, &vb, rb)
+        }
+        (Shape::Circle { radius: a }, Shape::Circle { radius: b }) => (pb - pa).magnitude() - a - b,
+        (Shape::Circle { radius }, _) => polygon_circle(&sb.vertices_at(pb, rb), pa, *radius),
+        (_, Shape::Circle { radius }) => polygon_circle(&sa.vertices_at(pa, ra), pb, *radius),
+        _ => {
+            let (va, vb) = (sa.vertices_at(pa, ra), sb.vertices_at(pb, rb));
+            Real::max(
+                Collision::max_separation(&va, &vb).0,
+                Collision::max_separation(&vb, &va).0,
+            )
+        }
+    }
+}
+
+fn polygon_circle(verts: &[Vec2], center: Vec2, radius: Real) -> Real {
+    let faces = Collision::max_separation(verts, &[center]).0 - radius;
+
+    // The axis through the closest vertex covers the corners.
+    let closest = verts
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            (*a - center)
+                .magnitude2()
+                .total_cmp(&(*b - center).magnitude2())
+        })
+        .unwrap_or(center);
+    let axis = (center - closest).unit();
+    let vertex = if axis.nearly_zero() {
+        Real::MIN
+    } else {
+        let farthest = verts.iter().map(|v| v.dot(axis)).fold(Real::MIN, Real::max);
+        center.dot(axis) - radius - farthest
+    };
+
+    Real::max(faces, vertex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swept_circle_hits() {
+        let ball = Shape::Circle { radius: 0.5 };
+        let sweep = Sweep {
+            start: Vec2::new(-10.0, 0.0),
+            end: Vec2::new(10.0, 0.0),
+            start_rotation: 0.0,
+            end_rotation: 0.0,
+        };
+        let other = Sweep::fixed(Vec2::new(0.0, 0.0), 0.0);
+
+        // Touches at x = -1, 9 out of 20 units in.
+        let t = time_of_impact(&ball, sweep, &ball, other, 0.01).unwrap();
+        assert!((t - 0.45).abs() < 0.001);
+
+        let beside = Sweep::fixed(Vec2::new(0.0, 1.5), 0.0);
+        assert!(time_of_impact(&ball, sweep, &ball, beside, 0.01).is_none());
+        assert_eq!(time_of_impact(&ball, other, &ball, other, 0.01), Some(0.0));
+    }
+
+    #[test]
+    fn box_hits_thin_wall() {
+        let block = Shape::Rect { w: 0.2, h: 0.2 };
+        let wall = Shape::Rect { w: 0.05, h: 4.0 };
+        let sweep = Sweep {
+            start: Vec2::new(-5.0, 0.0),
+            end: Vec2::new(5.0, 0.0),
+            start_rotation: 0.0,
+            end_rotation: 3.0,
+        };
+        let fixed = Sweep::fixed(Vec2::new(0.0, 0.0), 0.0);
+
+        let depth = 0.01;
+        let t = time_of_impact(&block, sweep, &wall, fixed, depth).unwrap();
+        let (position, rotation) = sweep.at(t);
+        let distance = separation(&block, position, rotation, &wall, Vec2::new(0.0, 0.0), 0.0);
+        assert!(distance <= -depth * 0.5 && distance >= -depth);
+
+        let ball = Shape::Circle { radius: 0.1 };
+        let t = time_of_impact(&ball, sweep, &wall, fixed, depth).unwrap();
+        // The ball touches at x = -0.125, 4.875 out of 10 units in.
+        assert!(t > 0.4875 && t < 0.489);
+    }
+}
@@ -1,45 +1,57 @@
 use std::cmp;
+use std::iter;
 use std::ops;
 
-pub const EPSILON: f32 = f32::EPSILON * 10.0;
+// Scalar of all the math, f64 with the f64 feature for big worlds that lose precision
+// far from the origin with f32.
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+#[cfg(feature = "f64")]
+pub type Real = f64;
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+pub const EPSILON: Real = Real::EPSILON * 10.0;
 
 #[derive(Default, Copy, Clone, Debug)]
 pub struct Vec2 {
-    pub x: f32,
-    pub y: f32,
+    pub x: Real,
+    pub y: Real,
 }
 
 impl Vec2 {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(x: Real, y: Real) -> Self {
         Vec2 { x, y }
     }
 
     #[inline]
     pub fn nearly_zero(&self) -> bool {
-        f32::abs(self.x) <= EPSILON && f32::abs(self.y) <= EPSILON
+        Real::abs(self.x) <= EPSILON && Real::abs(self.y) <= EPSILON
     }
 
     #[allow(dead_code)]
     #[inline]
     pub fn nearly_eq(&self, other: Vec2) -> bool {
-        f32::abs(self.x - other.x) <= EPSILON && f32::abs(self.y - other.y) <= EPSILON
+        Real::abs(self.x - other.x) <= EPSILON && Real::abs(self.y - other.y) <= EPSILON
     }
 
     #[inline]
-    pub fn magnitude2(&self) -> f32 {
+    pub fn magnitude2(&self) -> Real {
         self.x * self.x + self.y * self.y
     }
 
     #[inline]
-    pub fn magnitude(&self) -> f32 {
-        f32::sqrt(self.magnitude2())
+    pub fn magnitude(&self) -> Real {
+        Real::sqrt(self.magnitude2())
     }
 
     #[inline]
     pub fn unit(&self) -> Vec2 {
         let magnitude = self.magnitude();
 
-        if magnitude >= f32::EPSILON {
+        if magnitude >= Real::EPSILON {
             return *self * (1.0 / self.magnitude());
         }
 
@@ -47,22 +59,45 @@ impl Vec2 {
     }
 
     #[inline]
-    pub fn dot(&self, other: Vec2) -> f32 {
+    pub fn dot(&self, other: Vec2) -> Real {
         self.x * other.x + self.y * other.y
     }
 
+    // 2D cross product, the z component of the 3D cross product.
+    #[inline]
+    pub fn cross(&self, other: Vec2) -> Real {
+        self.x * other.y - self.y * other.x
+    }
+
+    // Vector times a z axis vector of length s, perpendicular to self.
+    #[inline]
+    pub fn cross_scalar(&self, s: Real) -> Vec2 {
+        Vec2::new(s * self.y, -s * self.x)
+    }
+
+    // z axis vector of length s times the vector, like angular velocity times the arm
+    // from the center of mass gives the velocity of a point.
+    #[inline]
+    pub fn scalar_cross(s: Real, v: Vec2) -> Vec2 {
+        Vec2::new(-s * v.y, s * v.x)
+    }
+
+    // Straight line from self at t = 0 to other at t = 1.
+    #[inline]
+    pub fn lerp(&self, other: Vec2, t: Real) -> Vec2 {
+        *self + (other - *self) * t
+    }
+
     #[inline]
     pub fn reflect(&self, normal: Vec2) -> Vec2 {
         // direction (self) is pointing in, normal is pointing out
         *self - (normal * 2.0 * self.dot(normal))
     }
 
+    // Rot2 saves the sin and cos when rotating many vectors by the same angle.
     #[inline]
-    pub fn rotate(&self, rads: f32) -> Vec2 {
-        Vec2 {
-            x: self.x * rads.cos() - self.y * rads.sin(),
-            y: self.x * rads.sin() + self.y * rads.cos(),
-        }
+    pub fn rotate(&self, rads: Real) -> Vec2 {
+        Rot2::new(rads).rotate(*self)
     }
 
     // Get Vec2's perpendicular to this Vec2 in either direction.
@@ -138,34 +173,314 @@ impl ops::SubAssign for Vec2 {
     }
 }
 
-impl ops::Mul<f32> for Vec2 {
+impl ops::Mul<Real> for Vec2 {
     type Output = Self;
     #[inline]
-    fn mul(self, other: f32) -> Vec2 {
+    fn mul(self, other: Real) -> Vec2 {
         Vec2::new(self.x * other, self.y * other)
     }
 }
 
-impl ops::MulAssign<f32> for Vec2 {
+impl ops::MulAssign<Real> for Vec2 {
     #[inline]
-    fn mul_assign(&mut self, other: f32) {
+    fn mul_assign(&mut self, other: Real) {
         self.x *= other;
         self.y *= other;
     }
 }
 
-impl ops::Div<f32> for Vec2 {
+impl ops::Div<Real> for Vec2 {
     type Output = Self;
     #[inline]
-    fn div(self, other: f32) -> Vec2 {
+    fn div(self, other: Real) -> Vec2 {
         let factor = 1.0 / other;
         Vec2::new(self.x * factor, self.y * factor)
     }
 }
 
+// Componentwise.
+impl ops::Mul for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x * other.x, self.y * other.y)
+    }
+}
+
+impl ops::Div for Vec2 {
+    type Output = Self;
+    #[inline]
+    fn div(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x / other.x, self.y / other.y)
+    }
+}
+
+// x is 0 and y is 1.
+impl ops::Index<usize> for Vec2 {
+    type Output = Real;
+    #[inline]
+    fn index(&self, i: usize) -> &Real {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vec2 index {i} out of range"),
+        }
+    }
+}
+
+impl ops::IndexMut<usize> for Vec2 {
+    #[inline]
+    fn index_mut(&mut self, i: usize) -> &mut Real {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vec2 index {i} out of range"),
+        }
+    }
+}
+
+impl iter::Sum for Vec2 {
+    fn sum<I: Iterator<Item = Vec2>>(iter: I) -> Vec2 {
+        iter.fold(Vec2::default(), |sum, v| sum + v)
+    }
+}
+
+impl From<(Real, Real)> for Vec2 {
+    #[inline]
+    fn from((x, y): (Real, Real)) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
+impl From<Vec2> for (Real, Real) {
+    #[inline]
+    fn from(v: Vec2) -> Self {
+        (v.x, v.y)
+    }
+}
+
+// Rotation by an angle, keeping the sin and cos so they're worked out once.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rot2 {
+    pub cos: Real,
+    pub sin: Real,
+}
+
+impl Default for Rot2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Rot2 {
+    pub const IDENTITY: Rot2 = Rot2 { cos: 1.0, sin: 0.0 };
+
+    #[inline]
+    pub fn new(rads: Real) -> Self {
+        let (sin, cos) = rads.sin_cos();
+        Self { cos, sin }
+    }
+
+    #[inline]
+    pub fn angle(&self) -> Real {
+        self.sin.atan2(self.cos)
+    }
+
+    #[inline]
+    pub fn rotate(&self, v: Vec2) -> Vec2 {
+        Vec2::new(
+            self.cos * v.x - self.sin * v.y,
+            self.sin * v.x + self.cos * v.y,
+        )
+    }
+
+    // Rotates back the other way, the same as self.inverse().rotate(v).
+    #[inline]
+    pub fn inverse_rotate(&self, v: Vec2) -> Vec2 {
+        Vec2::new(
+            self.cos * v.x + self.sin * v.y,
+            -self.sin * v.x + self.cos * v.y,
+        )
+    }
+
+    #[inline]
+    pub fn inverse(&self) -> Rot2 {
+        Rot2 {
+            cos: self.cos,
+            sin: -self.sin,
+        }
+    }
+}
+
+// Rotating by a * b is rotating by b and then by a.
+impl ops::Mul for Rot2 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: Rot2) -> Rot2 {
+        Rot2 {
+            cos: self.cos * other.cos - self.sin * other.sin,
+            sin: self.sin * other.cos + self.cos * other.sin,
+        }
+    }
+}
+
+impl ops::Mul<Vec2> for Rot2 {
+    type Output = Vec2;
+    #[inline]
+    fn mul(self, v: Vec2) -> Vec2 {
+        self.rotate(v)
+    }
+}
+
+impl From<Rot2> for Mat2 {
+    fn from(rot: Rot2) -> Self {
+        Mat2::new(rot.cos, -rot.sin, rot.sin, rot.cos)
+    }
+}
+
+// 2x2 matrix stored by columns.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Mat2 {
+    pub x: Vec2, // First column.
+    pub y: Vec2,
+}
+
+impl Mat2 {
+    pub const IDENTITY: Mat2 = Mat2 {
+        x: Vec2 { x: 1.0, y: 0.0 },
+        y: Vec2 { x: 0.0, y: 1.0 },
+    };
+
+    // Row by row, [a11 a12; a21 a22].
+    #[inline]
+    pub fn new(a11: Real, a12: Real, a21: Real, a22: Real) -> Self {
+        Self {
+            x: Vec2::new(a11, a21),
+            y: Vec2::new(a12, a22),
+        }
+    }
+
+    #[inline]
+    pub fn determinant(&self) -> Real {
+        self.x.cross(self.y)
+    }
+
+    #[inline]
+    pub fn transpose(&self) -> Mat2 {
+        Mat2::new(self.x.x, self.x.y, self.y.x, self.y.y)
+    }
+
+    // None when the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat2> {
+        let det = self.determinant();
+        if det.abs() <= Real::EPSILON {
+            return None;
+        }
+        let factor = 1.0 / det;
+        Some(Mat2::new(
+            self.y.y * factor,
+            -self.y.x * factor,
+            -self.x.y * factor,
+            self.x.x * factor,
+        ))
+    }
+
+    // x in self * x = b, without making the inverse. Zero when the matrix is singular,
+    // as between two infinite mass bodies.
+    pub fn solve(&self, b: Vec2) -> Vec2 {
+        let det = self.determinant();
+        if det.abs() <= Real::EPSILON {
+            return Vec2::default();
+        }
+        Vec2::new(
+            self.y.y * b.x - self.y.x * b.y,
+            self.x.x * b.y - self.x.y * b.x,
+        ) * (1.0 / det)
+    }
+}
+
+impl ops::Mul<Vec2> for Mat2 {
+    type Output = Vec2;
+    #[inline]
+    fn mul(self, v: Vec2) -> Vec2 {
+        self.x * v.x + self.y * v.y
+    }
+}
+
+impl ops::Mul for Mat2 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: Mat2) -> Mat2 {
+        Mat2 {
+            x: self * other.x,
+            y: self * other.y,
+        }
+    }
+}
+
+// Rotation followed by a move, from a body's space to World space.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Transform2 {
+    pub position: Vec2,
+    pub rotation: Rot2,
+}
+
+impl Transform2 {
+    #[inline]
+    pub fn new(position: Vec2, rads: Real) -> Self {
+        Self {
+            position,
+            rotation: Rot2::new(rads),
+        }
+    }
+
+    // Body space point to World space.
+    #[inline]
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        self.rotation.rotate(point) + self.position
+    }
+
+    // Directions only turn, they don't move.
+    #[inline]
+    pub fn apply_vector(&self, v: Vec2) -> Vec2 {
+        self.rotation.rotate(v)
+    }
+
+    // World space point to body space.
+    #[inline]
+    pub fn inverse_apply(&self, point: Vec2) -> Vec2 {
+        self.rotation.inverse_rotate(point - self.position)
+    }
+
+    #[inline]
+    pub fn inverse_apply_vector(&self, v: Vec2) -> Vec2 {
+        self.rotation.inverse_rotate(v)
+    }
+
+    pub fn inverse(&self) -> Transform2 {
+        let rotation = self.rotation.inverse();
+        Transform2 {
+            position: -rotation.rotate(self.position),
+            rotation,
+        }
+    }
+}
+
+// Applying a * b is applying b and then a.
+impl ops::Mul for Transform2 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: Transform2) -> Transform2 {
+        Transform2 {
+            position: self.apply(other.position),
+            rotation: self.rotation * other.rotation,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Vec2;
+    use super::*;
     #[test]
     fn add() {
         let a = Vec2::new(10.0, 25.0);
@@ -207,11 +522,54 @@ mod tests {
     #[test]
     fn rotate() {
         let a = Vec2::new(1.0, 0.0);
-        let b = a.rotate(std::f32::consts::PI);
+        let b = a.rotate(consts::PI);
         assert_eq!(b.x, -1.0);
-        assert!(b.y.abs() <= std::f32::EPSILON); // Rust has nothing for this? :/
-        let c = a.rotate(std::f32::consts::PI * 0.5);
-        assert!(c.x.abs() <= std::f32::EPSILON);
+        assert!(b.y.abs() <= Real::EPSILON); // Rust has nothing for this? :/
+        let c = a.rotate(consts::PI * 0.5);
+        assert!(c.x.abs() <= Real::EPSILON);
         assert_eq!(c.y, 1.0);
     }
+
+    #[test]
+    fn products_and_conversions() {
+        let a = Vec2::new(1.0, 2.0);
+        let b: Vec2 = (3.0, -1.0).into();
+        assert_eq!(a.cross(b), -7.0);
+        assert_eq!(a.cross(a), 0.0);
+        // Both ways around z give the same perpendicular, opposite signs.
+        assert_eq!(a.cross_scalar(2.0), -Vec2::scalar_cross(2.0, a));
+        assert_eq!(a.cross_scalar(1.0), a.normal_positive());
+        assert_eq!(a.lerp(b, 0.5), Vec2::new(2.0, 0.5));
+        assert_eq!(a * b, Vec2::new(3.0, -2.0));
+        assert_eq!(a / Vec2::new(2.0, 4.0), Vec2::new(0.5, 0.5));
+        assert_eq!((a[0], a[1]), (1.0, 2.0));
+        assert_eq!([a, b].into_iter().sum::<Vec2>(), Vec2::new(4.0, 1.0));
+        assert_eq!(<(Real, Real)>::from(a), (1.0, 2.0));
+    }
+
+    #[test]
+    fn rotations_and_transforms() {
+        let quarter = Rot2::new(consts::PI * 0.5);
+        let v = Vec2::new(1.0, 0.0);
+        assert!(quarter.rotate(v).nearly_eq(Vec2::new(0.0, 1.0)));
+        assert!(quarter.inverse_rotate(quarter.rotate(v)).nearly_eq(v));
+        assert!(((quarter * quarter).angle().abs() - consts::PI).abs() < 0.0001);
+        assert!((Mat2::from(quarter) * v).nearly_eq(quarter * v));
+
+        let m = Mat2::new(4.0, 1.0, 1.0, 3.0);
+        let x = m.solve(Vec2::new(1.0, 2.0));
+        assert!((m * x).nearly_eq(Vec2::new(1.0, 2.0)));
+        assert!((m * m.inverse().unwrap()).x.nearly_eq(Mat2::IDENTITY.x));
+        assert!(Mat2::new(1.0, 2.0, 2.0, 4.0).inverse().is_none());
+        assert_eq!(m.transpose().transpose(), m);
+
+        let body = Transform2::new(Vec2::new(2.0, 1.0), consts::PI * 0.5);
+        let point = Vec2::new(1.0, 1.0);
+        let world = body.apply(point);
+        assert!(world.nearly_eq(Vec2::new(1.0, 2.0)));
+        assert!(body.inverse_apply(world).nearly_eq(point));
+        assert!(body.inverse().apply(world).nearly_eq(point));
+        let nested = body * Transform2::new(Vec2::new(1.0, 0.0), 0.0);
+        assert!(nested.apply(Vec2::new(0.0, 1.0)).nearly_eq(world));
+    }
 }
@@ -0,0 +1,1676 @@
+use crate::broadphase::{Aabb, Broadphase, SortedAabbs};
+use crate::collision::Collision;
+use crate::filter::Filter;
+use crate::force::{ForceRegistry, ForceTarget};
+use crate::island::{Islands, SleepSettings};
+use crate::joint::Joint;
+use crate::manifold::Manifold;
+use crate::physics::{BodyType, Integrator, Physics};
+use crate::shape::Shape;
+use crate::solver::ContactSolver;
+use crate::toi::{time_of_impact, Sweep};
+use crate::vec2::{Real, Vec2};
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+// Refers to a body in a World. Handles of removed bodies stay invalid even after their
+// slot is reused, thanks to the generation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyHandle {
+    slot: u32,
+    generation: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JointHandle(u32);
+
+// Two bodies touching at the end of a step.
+#[derive(Copy, Clone, Debug)]
+pub struct Contact {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub manifold: Manifold, // Normal points from a to b.
+    pub sensor: bool,       // One of them is a sensor, nothing was pushed.
+    // Applied by the solver during the step, summed over the contact points.
+    pub normal_impulse: Real,
+    pub tangent_impulse: Real,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ContactPhase {
+    Begin,   // Started touching in this step.
+    Persist, // Touching in the last step too.
+    End,     // Touching in the last step but not anymore.
+}
+
+// A change in a Contact, see World::contact_events. Ended contacts keep the values from
+// the last step they touched in.
+#[derive(Copy, Clone, Debug)]
+pub struct ContactEvent {
+    pub phase: ContactPhase,
+    pub contact: Contact,
+}
+
+// Where a ray first touches a body. fraction is how far along the ray, from 0 to 1, and
+// normal points out of the body that was hit.
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+    pub body: BodyHandle,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub fraction: Real,
+}
+
+// Where a moving shape first touches a body, see World::shape_cast. fraction is how far
+// along the translation, from 0 to 1, and normal points out of the body that was hit.
+#[derive(Copy, Clone, Debug)]
+pub struct ShapeHit {
+    pub body: BodyHandle,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub fraction: Real,
+    pub position: Vec2, // Of the moving shape when it touches.
+}
+
+// Decides whether two bodies that passed their Filters collide, see
+// World::set_contact_filter.
+type ContactFilter = Box<dyn FnMut(BodyHandle, &Physics, BodyHandle, &Physics) -> bool>;
+
+struct Slot {
+    generation: u32,
+    index: Option<usize>, // Into the body arrays, None when the slot is free.
+}
+
+// Owns the bodies and steps them: forces, contacts, solver.
+// Units are up to the user, the solver tolerances default to meters.
+pub struct World {
+    gravity: Vec2,
+    pub solver: ContactSolver,
+    pub broadphase: Broadphase,
+    pub integrator: Integrator, // Unless the body picks its own.
+    pub forces: ForceRegistry,
+    pub joint_baumgarte: Real, // Fraction of the joint position errors removed per step.
+    // How fast bodies can move away from a one-way body's side and still be pushed by it,
+    // so resting bodies can jitter.
+    pub one_way_tolerance: Real,
+    pub sleep: SleepSettings,
+
+    // Bodies are kept packed so the solver can work on a slice, removal swaps the last
+    // body into the hole and slots map the handles to the moving indices.
+    physics: Vec<Physics>,
+    shapes: Vec<Shape>,
+    handles: Vec<BodyHandle>,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+
+    // Joints of removed bodies stay until removed, they are skipped meanwhile.
+    joints: Vec<(JointHandle, Joint)>,
+    next_joint: u32,
+
+    contact_filter: Option<ContactFilter>,
+    // Overlapping pairs that pass through each other until they separate, by lower
+    // handle first.
+    passing: HashSet<(BodyHandle, BodyHandle)>,
+
+    contacts: Vec<Contact>,
+    contact_events: Vec<ContactEvent>,
+
+    // Bounding boxes for the queries, built by the first query after anything could have
+    // moved and kept until the next change.
+    query_aabbs: RefCell<Option<SortedAabbs>>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new(Vec2::new(0.0, 9.81))
+    }
+}
+
+impl World {
+    pub fn new(gravity: Vec2) -> Self {
+        Self {
+            gravity,
+            solver: ContactSolver::default(),
+            broadphase: Broadphase::default(),
+            integrator: Integrator::default(),
+            forces: ForceRegistry::default(),
+            joint_baumgarte: 0.2,
+            one_way_tolerance: 1.0,
+            sleep: SleepSettings::default(),
+            physics: Vec::new(),
+            shapes: Vec::new(),
+            handles: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            joints: Vec::new(),
+            next_joint: 0,
+            contact_filter: None,
+            passing: HashSet::new(),
+            contacts: Vec::new(),
+            contact_events: Vec::new(),
+            query_aabbs: RefCell::new(None),
+        }
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    pub fn add_body(&mut self, physics: Physics, shape: Shape) -> BodyHandle {
+        let index = self.physics.len();
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    index: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        self.slots[slot as usize].index = Some(index);
+        let handle = BodyHandle {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        };
+
+        self.physics.push(physics);
+        self.shapes.push(shape);
+        self.handles.push(handle);
+        self.query_aabbs.take();
+        handle
+    }
+
+    // Gives the body back, None if it was already removed. Bodies touching it or joined to
+    // it are woken up, they might have been resting on it.
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<(Physics, Shape)> {
+        let index = self.index(handle)?;
+        let last = self.physics.len() - 1;
+
+        let contacts = self.contacts.iter().map(|c| (c.a, c.b));
+        let joints = self.joints.iter().map(|(_, j)| (j.a, j.b));
+        let others: Vec<BodyHandle> = contacts
+            .chain(joints)
+            .filter_map(|(a, b)| match (a == handle, b == handle) {
+                (true, false) => Some(b),
+                (false, true) => Some(a),
+                _ => None,
+            })
+            .collect();
+        for other in others {
+            if let Some(physics) = self.physics_mut(other) {
+                physics.wake();
+            }
+        }
+
+        let slot = &mut self.slots[handle.slot as usize];
+        slot.index = None;
+        slot.generation += 1;
+        self.free_slots.push(handle.slot);
+
+        self.handles.swap_remove(index);
+        if index != last {
+            self.slots[self.handles[index].slot as usize].index = Some(index);
+        }
+        self.solver.remove_body(index, last);
+        self.contacts.retain(|c| c.a != handle && c.b != handle);
+        self.query_aabbs.take();
+
+        Some((
+            self.physics.swap_remove(index),
+            self.shapes.swap_remove(index),
+        ))
+    }
+
+    pub fn contains(&self, handle: BodyHandle) -> bool {
+        self.index(handle).is_some()
+    }
+
+    pub fn physics(&self, handle: BodyHandle) -> Option<&Physics> {
+        self.index(handle).map(|i| &self.physics[i])
+    }
+
+    pub fn physics_mut(&mut self, handle: BodyHandle) -> Option<&mut Physics> {
+        self.query_aabbs.take();
+        self.index(handle).map(|i| &mut self.physics[i])
+    }
+
+    pub fn shape(&self, handle: BodyHandle) -> Option<&Shape> {
+        self.index(handle).map(|i| &self.shapes[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.physics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.physics.is_empty()
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &Physics, &Shape)> {
+        self.handles
+            .iter()
+            .zip(self.physics.iter().zip(self.shapes.iter()))
+            .map(|(h, (p, s))| (*h, p, s))
+    }
+
+    pub fn bodies_mut(&mut self) -> impl Iterator<Item = (BodyHandle, &mut Physics, &Shape)> {
+        self.query_aabbs.take();
+        self.handles
+            .iter()
+            .zip(self.physics.iter_mut().zip(self.shapes.iter()))
+            .map(|(h, (p, s))| (*h, p, s))
+    }
+
+    // Converts a world space point to body space, for joint anchors.
+    pub fn local_point(&self, handle: BodyHandle, point: Vec2) -> Option<Vec2> {
+        let physics = self.physics(handle)?;
+        Some(physics.transform().inverse_apply(point))
+    }
+
+    pub fn add_joint(&mut self, joint: Joint) -> JointHandle {
+        self.next_joint += 1;
+        let handle = JointHandle(self.next_joint);
+        self.joints.push((handle, joint));
+        handle
+    }
+
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<Joint> {
+        let i = self.joints.iter().position(|(h, _)| *h == handle)?;
+        Some(self.joints.remove(i).1)
+    }
+
+    pub fn joint(&self, handle: JointHandle) -> Option<&Joint> {
+        self.joints
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, j)| j)
+    }
+
+    pub fn joint_mut(&mut self, handle: JointHandle) -> Option<&mut Joint> {
+        self.joints
+            .iter_mut()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, j)| j)
+    }
+
+    // Called for every pair of bodies whose bounding boxes overlap and whose Filters let
+    // them collide, returning false makes them pass through each other.
+    pub fn set_contact_filter<F>(&mut self, filter: F)
+    where
+        F: FnMut(BodyHandle, &Physics, BodyHandle, &Physics) -> bool + 'static,
+    {
+        self.contact_filter = Some(Box::new(filter));
+    }
+
+    pub fn clear_contact_filter(&mut self) {
+        self.contact_filter = None;
+    }
+
+    // Lets the two bodies pass through each other until they no longer overlap, like the
+    // player dropping through a one-way platform.
+    pub fn disable_contact(&mut self, a: BodyHandle, b: BodyHandle) {
+        self.passing.insert((a.min(b), a.max(b)));
+        for handle in [a, b] {
+            if let Some(physics) = self.physics_mut(handle) {
+                physics.wake();
+            }
+        }
+    }
+
+    // Contacts found during the last step.
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    pub fn contacts_with(&self, handle: BodyHandle) -> impl Iterator<Item = &Contact> {
+        self.contacts
+            .iter()
+            .filter(move |c| c.a == handle || c.b == handle)
+    }
+
+    // Contacts that began, persisted or ended during the last step. Contacts of removed
+    // bodies end without an event.
+    pub fn contact_events(&self) -> &[ContactEvent] {
+        &self.contact_events
+    }
+
+    // Closest body along the ray. direction doesn't have to be a unit vector. The queries
+    // only see bodies whose Filter collides with filter, Filter::default() sees the ones
+    // the default body would collide with.
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: Real,
+        filter: &Filter,
+    ) -> Option<RayHit> {
+        self.raycast_all(origin, direction, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    // Every body along the ray, closest first. Sensors aren't hit, here or in shape_cast.
+    pub fn raycast_all(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: Real,
+        filter: &Filter,
+    ) -> Vec<RayHit> {
+        if direction.nearly_zero() || max_distance <= 0.0 {
+            return Vec::new();
+        }
+        let direction = direction.unit();
+        let area = Aabb::from_points(&[origin, origin + direction * max_distance]);
+
+        let mut hits: Vec<RayHit> = self
+            .overlapping(&area, filter)
+            .into_iter()
+            .filter(|&i| !self.physics[i].sensor)
+            .filter_map(|i| {
+                let (distance, normal) =
+                    self.shapes[i].raycast(&self.physics[i], origin, direction, max_distance)?;
+                Some(RayHit {
+                    body: self.handles[i],
+                    point: origin + direction * distance,
+                    normal,
+                    fraction: distance / max_distance,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+        hits
+    }
+
+    // Bodies whose shapes contain the point.
+    pub fn query_point(&self, point: Vec2, filter: &Filter) -> Vec<BodyHandle> {
+        self.overlapping(&Aabb::new(point, point), filter)
+            .into_iter()
+            .filter(|&i| self.shapes[i].contains_point(&self.physics[i], point))
+            .map(|i| self.handles[i])
+            .collect()
+    }
+
+    // Bodies whose bounding boxes overlap the area.
+    pub fn query_aabb(&self, area: &Aabb, filter: &Filter) -> Vec<BodyHandle> {
+        self.overlapping(area, filter)
+            .into_iter()
+            .map(|i| self.handles[i])
+            .collect()
+    }
+
+    // First body the shape touches when moved by translation, without rotating.
+    pub fn shape_cast(
+        &self,
+        shape: &Shape,
+        position: Vec2,
+        rotation: Real,
+        translation: Vec2,
+        filter: &Filter,
+    ) -> Option<ShapeHit> {
+        let sweep = Sweep {
+            start: position,
+            end: position + translation,
+            start_rotation: rotation,
+            end_rotation: rotation,
+        };
+        let area = shape
+            .aabb_at(sweep.start, rotation)
+            .union(&shape.aabb_at(sweep.end, rotation));
+
+        self.overlapping(&area, filter)
+            .into_iter()
+            .filter(|&i| !self.physics[i].sensor)
+            .filter_map(|i| {
+                let physics = &self.physics[i];
+                let fixed = Sweep::fixed(physics.position, physics.rotation);
+                let t = time_of_impact(shape, sweep, &self.shapes[i], fixed, self.solver.slop)?;
+
+                // The contact at the time of impact gives the point and normal.
+                let mut cast = Physics::new_static(sweep.at(t).0, 0.0);
+                cast.rotation = rotation;
+                let collision = Collision::detect(shape, &self.shapes[i], &cast, physics)?;
+                let manifold = collision.manifold();
+                Some(ShapeHit {
+                    body: self.handles[i],
+                    point: manifold.points().first()?.position,
+                    normal: -manifold.normal(),
+                    fraction: t,
+                    position: cast.position,
+                })
+            })
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    // Forces applied since the last step are used up, gravity and the registered forces
+    // are added here. Sleeping bodies are skipped until something wakes them.
+    pub fn step(&mut self, dt: Real) {
+        let staged = self.integrate_in_stages(dt);
+
+        for (i, physics) in self.physics.iter_mut().enumerate() {
+            physics.previous_position = physics.position;
+            physics.previous_rotation = physics.rotation;
+            if physics.is_sleeping() || staged.contains(&i) {
+                continue;
+            }
+
+            if physics.is_dynamic() {
+                physics.apply_force(self.gravity * physics.mass);
+            }
+            physics.integrate_velocity_with(physics.integrator.unwrap_or(self.integrator), dt);
+        }
+
+        // Joints whose bodies still exist, with the body indices. A joint to a moving
+        // body wakes the other one up, joints between resting bodies are skipped.
+        let slots = &self.slots;
+        let physics = &mut self.physics;
+        let mut joints: Vec<(usize, usize, &mut Joint)> = self
+            .joints
+            .iter_mut()
+            .filter_map(|(_, joint)| {
+                let (a, b) = (index(slots, joint.a)?, index(slots, joint.b)?);
+                if a == b || !(is_active(&physics[a]) || is_active(&physics[b])) {
+                    return None;
+                }
+                physics[a].wake();
+                physics[b].wake();
+                Some((a, b, joint))
+            })
+            .collect();
+        let unconnected: HashSet<(usize, usize)> = joints
+            .iter()
+            .filter(|(_, _, joint)| !joint.collide_connected)
+            .map(|&(a, b, _)| (a.min(b), a.max(b)))
+            .collect();
+
+        // Resting bodies keep the contacts they had when they fell asleep.
+        let previous = std::mem::take(&mut self.contacts);
+        let (slots, physics) = (&self.slots, &self.physics);
+        let resting: Vec<Contact> = previous
+            .iter()
+            .filter(|c| match (index(slots, c.a), index(slots, c.b)) {
+                (Some(a), Some(b)) => !is_active(&physics[a]) && !is_active(&physics[b]),
+                _ => false,
+            })
+            .copied()
+            .collect();
+        let aabbs: Vec<Aabb> = self
+            .shapes
+            .iter()
+            .zip(self.physics.iter())
+            .map(|(shape, physics)| shape.aabb(physics))
+            .collect();
+        let mut touching = Vec::new();
+        let mut solved = Vec::new(); // Indices of the contacts given to the solver.
+        let mut still_passing = HashSet::new();
+        for (i, j) in self.broadphase.pairs(&aabbs) {
+            // Static and kinematic bodies don't push each other, and neither do bodies
+            // at rest. Sensors don't sense each other.
+            let sensor = self.physics[i].sensor || self.physics[j].sensor;
+            if !(self.physics[i].is_dynamic() || self.physics[j].is_dynamic())
+                || !(is_active(&self.physics[i]) || is_active(&self.physics[j]))
+                || (self.physics[i].sensor && self.physics[j].sensor)
+                || !should_collide(
+                    &mut self.contact_filter,
+                    &self.handles,
+                    &self.physics,
+                    (i, j),
+                    &unconnected,
+                )
+            {
+                continue;
+            }
+            let collisions = Collision::detect_all(
+                &self.shapes[i],
+                &self.shapes[j],
+                &self.physics[i],
+                &self.physics[j],
+            );
+            if collisions.is_empty() {
+                continue;
+            }
+
+            // Once a body starts passing through it has to get all the way through.
+            let pair = (
+                self.handles[i].min(self.handles[j]),
+                self.handles[i].max(self.handles[j]),
+            );
+            let tolerance = self.one_way_tolerance;
+            if self.passing.contains(&pair)
+                || !collisions.iter().all(|c| {
+                    one_way_allows(&self.physics[i], &self.physics[j], c.manifold(), tolerance)
+                })
+            {
+                self.passing.insert(pair);
+                still_passing.insert(pair);
+                continue;
+            }
+            if !sensor {
+                self.physics[i].wake();
+                self.physics[j].wake();
+                touching.push((i, j));
+            }
+            // Compound shapes can touch in several places, each gets a contact.
+            for collision in collisions {
+                if !sensor {
+                    solved.push(self.contacts.len());
+                    self.solver.add_contact(i, j, collision.manifold());
+                }
+                self.contacts.push(Contact {
+                    a: self.handles[i],
+                    b: self.handles[j],
+                    manifold: *collision.manifold(),
+                    sensor,
+                    normal_impulse: 0.0,
+                    tangent_impulse: 0.0,
+                });
+            }
+        }
+        self.passing = still_passing;
+        let physics = &self.physics;
+        self.contacts.extend(resting.into_iter().filter(|c| {
+            let (a, b) = (index(slots, c.a), index(slots, c.b));
+            a.zip(b)
+                .is_some_and(|(a, b)| !is_active(&physics[a]) && !is_active(&physics[b]))
+        }));
+
+        self.solver.begin_velocities(&mut self.physics);
+        for (a, b, joint) in joints.iter_mut() {
+            joint.prepare(&self.physics, *a, *b, dt, self.joint_baumgarte);
+            if self.solver.warm_starting {
+                joint.warm_start(&mut self.physics);
+            }
+        }
+        for _ in 0..self.solver.velocity_iterations {
+            for (_, _, joint) in joints.iter_mut() {
+                joint.solve(&mut self.physics);
+            }
+            self.solver.iterate_velocities(&mut self.physics);
+        }
+        self.solver.end_velocities();
+        for (&k, (normal, tangent)) in solved.iter().zip(self.solver.contact_impulses()) {
+            self.contacts[k].normal_impulse = normal;
+            self.contacts[k].tangent_impulse = tangent;
+        }
+        touching.extend(joints.iter().map(|&(a, b, _)| (a, b)));
+
+        for physics in self.physics.iter_mut() {
+            if !physics.is_sleeping() {
+                physics.integrate_position(dt);
+            }
+        }
+        self.solver.solve_positions(&mut self.physics);
+        self.move_bullets_to_impact(&unconnected);
+        self.update_sleep(dt, &touching);
+        self.update_contact_events(&previous);
+        self.query_aabbs.take();
+    }
+
+    // Compares the contacts with the ones from the previous step, by body pair.
+    fn update_contact_events(&mut self, previous: &[Contact]) {
+        let pair = |c: &Contact| (c.a.min(c.b), c.a.max(c.b));
+        let before: HashSet<_> = previous.iter().map(pair).collect();
+        let now: HashSet<_> = self.contacts.iter().map(pair).collect();
+
+        self.contact_events.clear();
+        for contact in &self.contacts {
+            let phase = if before.contains(&pair(contact)) {
+                ContactPhase::Persist
+            } else {
+                ContactPhase::Begin
+            };
+            self.contact_events.push(ContactEvent {
+                phase,
+                contact: *contact,
+            });
+        }
+        for contact in previous.iter().filter(|c| !now.contains(&pair(c))) {
+            self.contact_events.push(ContactEvent {
+                phase: ContactPhase::End,
+                contact: *contact,
+            });
+        }
+    }
+
+    // Puts islands, bodies connected by contacts or joints, to sleep once all of their
+    // bodies have been resting long enough. Static and kinematic bodies don't connect
+    // islands, a box on the ground sleeps on its own.
+    fn update_sleep(&mut self, dt: Real, connected: &[(usize, usize)]) {
+        // Bodies put to sleep by hand stay asleep until something wakes them.
+        let settings = self.sleep;
+        if !settings.enabled {
+            return;
+        }
+
+        let mut islands = Islands::new(self.physics.len());
+        for &(a, b) in connected {
+            if self.physics[a].is_dynamic() && self.physics[b].is_dynamic() {
+                islands.union(a, b);
+            }
+        }
+
+        // Shortest rest of the bodies in each island.
+        let mut rest = vec![Real::MAX; self.physics.len()];
+        for (i, physics) in self.physics.iter_mut().enumerate() {
+            if is_active(physics) && physics.is_dynamic() {
+                let time = physics.update_sleep_time(
+                    dt,
+                    settings.linear_velocity,
+                    settings.angular_velocity,
+                );
+                let island = islands.find(i);
+                rest[island] = rest[island].min(time);
+            }
+        }
+        for (i, physics) in self.physics.iter_mut().enumerate() {
+            if rest[islands.find(i)] >= settings.time {
+                physics.sleep();
+            }
+        }
+    }
+
+    // Continuous collision: bullets are moved back along their path to where they first
+    // touch another body, which stays at its new position. They keep their velocity, the
+    // contact is solved in the next step.
+    fn move_bullets_to_impact(&mut self, unconnected: &HashSet<(usize, usize)>) {
+        let is_bullet = |p: &Physics| p.bullet && p.is_dynamic() && !p.sensor;
+        if !self.physics.iter().any(is_bullet) {
+            return;
+        }
+
+        let sweep = |p: &Physics| Sweep {
+            start: p.previous_position,
+            end: p.position,
+            start_rotation: p.previous_rotation,
+            end_rotation: p.rotation,
+        };
+        let swept: Vec<Aabb> = self
+            .physics
+            .iter()
+            .zip(self.shapes.iter())
+            .map(|(physics, shape)| {
+                let aabb = shape.aabb(physics);
+                if is_bullet(physics) {
+                    aabb.union(&shape.aabb_at(physics.previous_position, physics.previous_rotation))
+                } else {
+                    aabb
+                }
+            })
+            .collect();
+
+        let mut impact = vec![1.0; self.physics.len()];
+        for (i, j) in self.broadphase.pairs(&swept) {
+            if !(is_bullet(&self.physics[i]) || is_bullet(&self.physics[j]))
+                || self.physics[i].sensor
+                || self.physics[j].sensor
+                || !should_collide(
+                    &mut self.contact_filter,
+                    &self.handles,
+                    &self.physics,
+                    (i, j),
+                    unconnected,
+                )
+            {
+                continue;
+            }
+            for (bullet, other) in [(i, j), (j, i)] {
+                let (pb, po) = (&self.physics[bullet], &self.physics[other]);
+                // Passing through, or coming from the wrong side of a one-way body.
+                let (hb, ho) = (self.handles[bullet], self.handles[other]);
+                let wrong_side = !one_way_approaching(po, pb, self.one_way_tolerance);
+                if !is_bullet(pb) || wrong_side || self.passing.contains(&(hb.min(ho), hb.max(ho)))
+                {
+                    continue;
+                }
+                let t = time_of_impact(
+                    &self.shapes[bullet],
+                    sweep(pb),
+                    &self.shapes[other],
+                    Sweep::fixed(po.position, po.rotation),
+                    self.solver.slop,
+                );
+                // Already touching at the start is for the contact solver.
+                if let Some(t) = t.filter(|t| *t > 0.0) {
+                    impact[bullet] = Real::min(impact[bullet], t);
+                }
+            }
+        }
+
+        for (physics, t) in self.physics.iter_mut().zip(impact) {
+            if t < 1.0 {
+                let (position, rotation) = sweep(physics).at(t);
+                physics.position = position;
+                physics.rotation = rotation;
+            }
+        }
+    }
+
+    // The registered forces for every body, and for the bodies integrated with Verlet or
+    // RK4 a whole step of forces and velocities. Those need the forces at several points
+    // in the step, twice for Verlet and four times for RK4, so the bodies are moved to each
+    // of them in turn and the forces evaluated there. The other bodies stay put and keep
+    // the forces from the start of the step. Gives the indices of the Verlet and RK4
+    // bodies, which only have their positions left to integrate.
+    // https://en.wikipedia.org/wiki/Verlet_integration#Velocity_Verlet
+    // https://en.wikipedia.org/wiki/Runge%E2%80%93Kutta_methods
+    fn integrate_in_stages(&mut self, dt: Real) -> Vec<usize> {
+        let staged: Vec<(usize, Integrator)> = (0..self.physics.len())
+            .filter_map(|i| {
+                let p = &self.physics[i];
+                let integrator = p.integrator.unwrap_or(self.integrator);
+                (p.is_dynamic() && !p.is_sleeping() && integrator != Integrator::SemiImplicitEuler)
+                    .then_some((i, integrator))
+            })
+            .collect();
+        if staged.is_empty() {
+            self.apply_registered_forces(dt);
+            return Vec::new();
+        }
+        let stages = if staged
+            .iter()
+            .any(|&(_, integrator)| integrator == Integrator::Rk4)
+        {
+            4
+        } else {
+            2
+        };
+
+        // Forces applied before the step count at every stage.
+        let start: Vec<(Vec2, Real, Vec2, Real, Vec2, Real)> = staged
+            .iter()
+            .map(|&(i, _)| {
+                let p = &self.physics[i];
+                (
+                    p.position,
+                    p.rotation,
+                    p.velocity,
+                    p.angular_velocity,
+                    p.forces,
+                    p.torque,
+                )
+            })
+            .collect();
+        // Velocity, angular velocity, acceleration and angular acceleration at each stage.
+        let mut k = vec![[(Vec2::default(), 0.0, Vec2::default(), 0.0); 4]; staged.len()];
+        let mut others = Vec::new();
+        for stage in 0..stages {
+            for (n, &(i, integrator)) in staged.iter().enumerate() {
+                let (x, r, v, w, forces, torque) = start[n];
+                let state = match (integrator, stage) {
+                    (_, 0) => (x, r, v, w),
+                    (Integrator::Rk4, _) => {
+                        let h = [0.0, 0.5, 0.5, 1.0][stage] * dt;
+                        let (dx, dr, dv, dw) = k[n][stage - 1];
+                        (x + dx * h, r + dr * h, v + dv * h, w + dw * h)
+                    }
+                    // Verlet moves to the end of the step, velocity dependent forces see the
+                    // Euler estimate of the new velocity. Past the second stage this is
+                    // only repeated for the RK4 bodies.
+                    _ => {
+                        let (_, _, a, alpha) = k[n][0];
+                        (
+                            x + v * dt + a * (0.5 * dt * dt),
+                            r + w * dt + alpha * (0.5 * dt * dt),
+                            v + a * dt,
+                            w + alpha * dt,
+                        )
+                    }
+                };
+                let p = &mut self.physics[i];
+                (p.position, p.rotation, p.velocity, p.angular_velocity) = state;
+                p.forces = forces;
+                p.torque = torque;
+            }
+            self.apply_registered_forces(dt);
+            if stage == 0 {
+                others = self.physics.iter().map(|p| (p.forces, p.torque)).collect();
+            }
+            for (n, &(i, _)) in staged.iter().enumerate() {
+                let p = &self.physics[i];
+                let acceleration = p.forces * p.inverse_mass + self.gravity;
+                let angular = p.torque * p.inverse_angular_mass;
+                k[n][stage] = (p.velocity, p.angular_velocity, acceleration, angular);
+            }
+        }
+
+        for (i, (forces, torque)) in others.into_iter().enumerate() {
+            self.physics[i].forces = forces;
+            self.physics[i].torque = torque;
+        }
+        for (n, &(i, integrator)) in staged.iter().enumerate() {
+            let (x, r, v, w, _, _) = start[n];
+            let p = &mut self.physics[i];
+            p.position = x;
+            p.rotation = r;
+            if integrator == Integrator::Rk4 {
+                let [k1, k2, k3, k4] = k[n];
+                let sixth = dt / 6.0;
+                p.set_step(
+                    dt,
+                    v + (k1.2 + k2.2 * 2.0 + k3.2 * 2.0 + k4.2) * sixth,
+                    w + (k1.3 + k2.3 * 2.0 + k3.3 * 2.0 + k4.3) * sixth,
+                    (k1.0 + k2.0 * 2.0 + k3.0 * 2.0 + k4.0) * sixth,
+                    (k1.1 + k2.1 * 2.0 + k3.1 * 2.0 + k4.1) * sixth,
+                );
+            } else {
+                // The position from the start of the step, the velocity from the average of
+                // the accelerations at its start and end.
+                let (k1, k2) = (k[n][0], k[n][1]);
+                p.set_step(
+                    dt,
+                    v + (k1.2 + k2.2) * (0.5 * dt),
+                    w + (k1.3 + k2.3) * (0.5 * dt),
+                    v * dt + k1.2 * (0.5 * dt * dt),
+                    w * dt + k1.3 * (0.5 * dt * dt),
+                );
+            }
+        }
+        staged.into_iter().map(|(i, _)| i).collect()
+    }
+
+    fn apply_registered_forces(&mut self, dt: Real) {
+        for (_, target, generator) in self.forces.generators.iter_mut() {
+            match target {
+                ForceTarget::All => {
+                    for (physics, shape) in self.physics.iter_mut().zip(self.shapes.iter()) {
+                        if !physics.is_sleeping() {
+                            generator.apply(physics, shape, dt);
+                        }
+                    }
+                }
+                ForceTarget::Bodies(handles) => {
+                    for i in handles.iter().filter_map(|h| index(&self.slots, *h)) {
+                        if !self.physics[i].is_sleeping() {
+                            generator.apply(&mut self.physics[i], &self.shapes[i], dt);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (_, spring) in self.forces.springs.iter() {
+            let (Some(a), Some(b)) = (index(&self.slots, spring.a), index(&self.slots, spring.b))
+            else {
+                continue;
+            };
+            // A spring between resting bodies would wake them up every step.
+            if a != b && (is_active(&self.physics[a]) || is_active(&self.physics[b])) {
+                let (pa, pb) = pair_mut(&mut self.physics, a, b);
+                spring.apply(pa, pb);
+            }
+        }
+    }
+
+    // Indices of the bodies whose bounding boxes overlap the area and whose Filters collide
+    // with filter, in index order.
+    fn overlapping(&self, area: &Aabb, filter: &Filter) -> Vec<usize> {
+        let mut query_aabbs = self.query_aabbs.borrow_mut();
+        let aabbs = query_aabbs.get_or_insert_with(|| {
+            SortedAabbs::new(
+                self.shapes
+                    .iter()
+                    .zip(self.physics.iter())
+                    .map(|(shape, physics)| shape.aabb(physics))
+                    .collect(),
+            )
+        });
+        let mut found = aabbs.overlapping(area);
+        found.retain(|&i| filter.should_collide(&self.physics[i].filter));
+        found
+    }
+
+    fn index(&self, handle: BodyHandle) -> Option<usize> {
+        index(&self.slots, handle)
+    }
+}
+
+// Bodies that can push others this step: awake dynamic bodies and moving kinematic ones.
+fn is_active(physics: &Physics) -> bool {
+    match physics.body_type() {
+        BodyType::Dynamic => !physics.is_sleeping(),
+        BodyType::Kinematic => !physics.velocity.nearly_zero() || physics.angular_velocity != 0.0,
+        BodyType::Static => false,
+    }
+}
+
+// One-way bodies only push bodies coming from their one_way side: the contact normal has
+// to point that way and one_way_approaching has to hold.
+fn one_way_allows(pa: &Physics, pb: &Physics, manifold: &Manifold, tolerance: Real) -> bool {
+    [(pa, pb, manifold.normal()), (pb, pa, -manifold.normal())]
+        .into_iter()
+        .all(|(platform, other, normal)| match platform.one_way {
+            Some(side) => {
+                let side = side.rotate(platform.rotation).unit();
+                normal.dot(side) >= 0.5 && one_way_approaching(platform, other, tolerance)
+            }
+            None => true,
+        })
+}
+
+// The other body isn't moving away from the platform's one_way side faster than the
+// tolerance, World::one_way_tolerance. Always true if the platform isn't one-way.
+fn one_way_approaching(platform: &Physics, other: &Physics, tolerance: Real) -> bool {
+    platform.one_way.is_none_or(|side| {
+        let side = side.rotate(platform.rotation).unit();
+        (other.velocity - platform.velocity).dot(side) <= tolerance
+    })
+}
+
+// Joints, Filters and the contact filter, in that order. Free function like index.
+fn should_collide(
+    contact_filter: &mut Option<ContactFilter>,
+    handles: &[BodyHandle],
+    physics: &[Physics],
+    (i, j): (usize, usize),
+    unconnected: &HashSet<(usize, usize)>,
+) -> bool {
+    let (pa, pb) = (&physics[i], &physics[j]);
+    if unconnected.contains(&(i.min(j), i.max(j))) || !pa.filter.should_collide(&pb.filter) {
+        return false;
+    }
+    match contact_filter {
+        Some(filter) => filter(handles[i], pa, handles[j], pb),
+        None => true,
+    }
+}
+
+// Free function so it can be used while other fields are borrowed.
+fn index(slots: &[Slot], handle: BodyHandle) -> Option<usize> {
+    let slot = slots.get(handle.slot as usize)?;
+    if slot.generation == handle.generation {
+        slot.index
+    } else {
+        None
+    }
+}
+
+// Mutable borrows of two different elements of the same slice.
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::{Buoyancy, Spring};
+
+    fn ball(x: Real, y: Real) -> (Physics, Shape) {
+        let shape = Shape::Circle { radius: 0.5 };
+        (
+            Physics::new(Vec2::new(x, y), 1.0, shape.rotational_inertia(), 0.0),
+            shape,
+        )
+    }
+
+    #[test]
+    fn removed_handles_stay_invalid() {
+        let mut world = World::default();
+        let (p, s) = ball(0.0, 0.0);
+        let a = world.add_body(p, s);
+        let (p, s) = ball(5.0, 0.0);
+        let b = world.add_body(p, s);
+
+        assert!(world.remove_body(a).is_some());
+        assert!(world.remove_body(a).is_none());
+        assert!(world.physics(a).is_none());
+        // b was moved into a's place, its handle still finds it.
+        assert_eq!(world.physics(b).unwrap().position.x, 5.0);
+
+        let (p, s) = ball(9.0, 0.0);
+        let c = world.add_body(p, s);
+        assert_ne!(a, c);
+        assert!(world.physics(a).is_none());
+        assert_eq!(world.physics(c).unwrap().position.x, 9.0);
+        assert_eq!(world.len(), 2);
+    }
+
+    #[test]
+    fn damped_spring_settles_at_rest_length() {
+        let mut world = World::new(Vec2::new(0.0, 0.0));
+        let (p, s) = ball(0.0, 0.0);
+        let a = world.add_body(p, s);
+        let (p, s) = ball(4.0, 0.0);
+        let b = world.add_body(p, s);
+        let mut spring = Spring::new(a, b, 20.0, 2.0);
+        spring.damping = 2.0;
+        world.forces.add_spring(spring);
+
+        for _ in 0..600 {
+            world.step(1.0 / 60.0);
+        }
+
+        let (pa, pb) = (world.physics(a).unwrap(), world.physics(b).unwrap());
+        assert!(((pb.position - pa.position).magnitude() - 2.0).abs() < 0.01);
+        // Internal forces, the center of mass stays put.
+        assert!(((pa.position + pb.position) * 0.5).nearly_eq(Vec2::new(2.0, 0.0)));
+    }
+
+    // Relative change in energy of a ball on a stiff spring, a few steps per period.
+    fn spring_energy_drift(integrator: Integrator) -> Real {
+        let mut world = World::new(Vec2::new(0.0, 0.0));
+        world.integrator = integrator;
+        let (p, s) = ball(0.0, 0.0);
+        let anchor = world.add_body(p, s);
+        world
+            .physics_mut(anchor)
+            .unwrap()
+            .set_body_type(BodyType::Static);
+        let (mut p, s) = ball(1.0, 0.0);
+        p.sensor = true; // Passes through the anchor.
+        p.can_sleep = false;
+        let k = 40.0 * p.mass;
+        let b = world.add_body(p, s);
+        world.forces.add_spring(Spring::new(anchor, b, k, 0.0));
+        let energy = |world: &World| {
+            let p = world.physics(b).unwrap();
+            0.5 * p.mass * p.velocity.magnitude2() + 0.5 * k * p.position.magnitude2()
+        };
+
+        // Period 2pi / sqrt(40), about a second.
+        let start = energy(&world);
+        for _ in 0..100 {
+            world.step(0.05);
+        }
+        (energy(&world) - start).abs() / start
+    }
+
+    #[test]
+    fn integrators_evaluate_forces_during_the_step() {
+        let euler = spring_energy_drift(Integrator::SemiImplicitEuler);
+        let verlet = spring_energy_drift(Integrator::VelocityVerlet);
+        let rk4 = spring_energy_drift(Integrator::Rk4);
+        assert!(verlet < 0.01 && verlet < euler, "{verlet} {euler}");
+        assert!(rk4 < 0.01, "{rk4}");
+    }
+
+    #[test]
+    fn generators_run_at_every_stage() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        // Where a body falling under a generator's gravity ends up, and how often the
+        // generator ran.
+        let fall = |integrator: Integrator| {
+            let mut world = World::new(Vec2::new(0.0, 0.0));
+            world.integrator = integrator;
+            let (p, s) = ball(0.0, 0.0);
+            let body = world.add_body(p, s);
+            let calls = Rc::new(Cell::new(0));
+            let counter = calls.clone();
+            world.forces.add(
+                ForceTarget::All,
+                move |physics: &mut Physics, _: &Shape, _: Real| {
+                    counter.set(counter.get() + 1);
+                    physics.apply_force(Vec2::new(0.0, 2.0) * physics.mass);
+                },
+            );
+            for _ in 0..10 {
+                world.step(0.1);
+            }
+            (world.physics(body).unwrap().position.y, calls.get())
+        };
+
+        let (euler, euler_calls) = fall(Integrator::SemiImplicitEuler);
+        let (verlet, verlet_calls) = fall(Integrator::VelocityVerlet);
+        let (rk4, rk4_calls) = fall(Integrator::Rk4);
+        assert_eq!((euler_calls, verlet_calls, rk4_calls), (10, 20, 40));
+        // The force only counts once per step however often it was evaluated, a constant
+        // acceleration of 2 for a second moves the body by 1.
+        assert!((verlet - 1.0).abs() < 1e-4, "{verlet}");
+        assert!((rk4 - 1.0).abs() < 1e-4, "{rk4}");
+        assert!((euler - 1.1).abs() < 1e-4, "{euler}");
+    }
+
+    #[test]
+    fn floats_in_denser_fluid() {
+        let gravity = Vec2::new(0.0, 9.81);
+        let mut world = World::new(gravity);
+        let shape = Shape::Rect { w: 1.0, h: 1.0 };
+        // Half as dense as the fluid, so it floats half submerged.
+        let body = world.add_body(
+            Physics::new(Vec2::new(0.0, -2.0), 500.0, shape.rotational_inertia(), 0.0),
+            shape,
+        );
+        world.forces.add(
+            ForceTarget::Bodies(vec![body]),
+            Buoyancy {
+                surface_y: 0.0,
+                density: 1000.0,
+                gravity,
+                linear_drag: 2000.0,
+            },
+        );
+
+        for _ in 0..600 {
+            world.step(1.0 / 60.0);
+        }
+
+        assert!(world.physics(body).unwrap().position.y.abs() < 0.01);
+    }
+
+    #[test]
+    fn elevator_lifts_box() {
+        let mut world = World::default();
+        let floor = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let mut platform = Physics::new_kinematic(Vec2::new(0.0, -0.25), 0.0);
+        platform.velocity = Vec2::new(0.0, -1.0);
+        let elevator = world.add_body(platform, Shape::Rect { w: 2.0, h: 0.5 });
+        let shape = Shape::Rect { w: 0.5, h: 0.5 };
+        let cargo = world.add_body(
+            Physics::new(Vec2::new(0.0, -0.75), 1.0, shape.rotational_inertia(), 0.0),
+            shape,
+        );
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+
+        // Two seconds at one meter per second up, gravity doesn't pull the platform down.
+        let platform = world.physics(elevator).unwrap();
+        assert!((platform.position.y - -2.25).abs() < 0.001);
+        let box_y = world.physics(cargo).unwrap().position.y;
+        assert!((box_y - -2.75).abs() < 0.02);
+        // Moving away from the floor, but kinematic against static isn't a contact anyway.
+        assert_eq!(world.contacts_with(floor).count(), 0);
+    }
+
+    #[test]
+    fn bullets_hit_thin_walls() {
+        let mut world = World::new(Vec2::new(0.0, 0.0));
+        let wall = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.0), 0.0),
+            Shape::Rect { w: 0.1, h: 4.0 },
+        );
+        let (mut p, s) = ball(-2.0, 0.0);
+        p.velocity = Vec2::new(300.0, 0.0);
+        let tunneling = world.add_body(p, s);
+        let (mut p, s) = ball(-2.0, 1.0);
+        p.velocity = Vec2::new(300.0, 0.0);
+        p.bullet = true;
+        let bullet = world.add_body(p, s);
+
+        world.step(1.0 / 60.0);
+        assert!(world.physics(tunneling).unwrap().position.x > 2.0);
+        let x = world.physics(bullet).unwrap().position.x;
+        assert!(x < -0.5 && x > -0.56);
+
+        world.step(1.0 / 60.0);
+        let physics = world.physics(bullet).unwrap();
+        assert!(physics.position.x < -0.5);
+        assert!(physics.velocity.x <= 0.0);
+        assert!(world.contacts_with(wall).any(|c| c.b == bullet));
+    }
+
+    #[test]
+    fn ball_lands_on_ground() {
+        let mut world = World::default();
+        let ground = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let (p, s) = ball(0.0, -3.0);
+        let ball = world.add_body(p, s);
+
+        for _ in 0..180 {
+            world.step(1.0 / 60.0);
+        }
+
+        let physics = world.physics(ball).unwrap();
+        assert!((physics.position.y - -0.5).abs() < 0.02);
+        assert!(physics.velocity.magnitude() < 0.05);
+        assert_eq!(world.physics(ground).unwrap().position, Vec2::new(0.0, 0.5));
+
+        let contact = world.contacts_with(ball).next().unwrap();
+        assert_eq!((contact.a, contact.b), (ground, ball));
+    }
+
+    #[test]
+    fn disabled_sleep_keeps_sleeping_bodies() {
+        let mut world = World::default();
+        world.sleep.enabled = false;
+        let (p, s) = ball(0.0, 0.0);
+        let asleep = world.add_body(p, s);
+        let (p, s) = ball(3.0, 0.0);
+        let awake = world.add_body(p, s);
+        world.physics_mut(asleep).unwrap().sleep();
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+        let (asleep, awake) = (
+            world.physics(asleep).unwrap(),
+            world.physics(awake).unwrap(),
+        );
+        assert!(asleep.is_sleeping());
+        assert_eq!(asleep.position, Vec2::new(0.0, 0.0));
+        assert!(!awake.is_sleeping());
+        assert!(awake.position.y > 1.0);
+    }
+
+    #[test]
+    fn resting_pile_sleeps_until_touched() {
+        let mut world = World::default();
+        world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let boxes: Vec<BodyHandle> = (0..3)
+            .map(|i| {
+                let shape = Shape::Rect { w: 1.0, h: 1.0 };
+                let position = Vec2::new(0.0, -0.5 - i as Real);
+                let physics = Physics::new(position, 1.0, shape.rotational_inertia(), 0.0);
+                world.add_body(physics, shape)
+            })
+            .collect();
+        let asleep = |world: &World| {
+            boxes
+                .iter()
+                .all(|b| world.physics(*b).unwrap().is_sleeping())
+        };
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(asleep(&world));
+        // Still reported as touching.
+        assert_eq!(world.contacts().len(), 3);
+        let top = world.physics(boxes[2]).unwrap().position;
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+        assert_eq!(world.physics(boxes[2]).unwrap().position, top);
+
+        // A ball landing on top wakes the whole stack.
+        let (p, s) = ball(0.0, -5.0);
+        let ball = world.add_body(p, s);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(boxes
+            .iter()
+            .all(|b| !world.physics(*b).unwrap().is_sleeping()));
+
+        // Settles down again, together with the ball.
+        for _ in 0..240 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(asleep(&world));
+        assert!(world.physics(ball).unwrap().is_sleeping());
+    }
+
+    #[test]
+    fn forces_wake_sleeping_bodies() {
+        let mut world = World::default();
+        world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let (p, s) = ball(0.0, -0.5);
+        let ball = world.add_body(p, s);
+
+        world.physics_mut(ball).unwrap().sleep();
+        world.step(1.0 / 60.0);
+        assert!(world.physics(ball).unwrap().is_sleeping());
+
+        world
+            .physics_mut(ball)
+            .unwrap()
+            .apply_impulse(Vec2::new(2.0, 0.0));
+        world.step(1.0 / 60.0);
+        let physics = world.physics(ball).unwrap();
+        assert!(!physics.is_sleeping());
+        assert!(physics.position.x > 0.0);
+
+        // Bodies that can't sleep stay awake.
+        world.physics_mut(ball).unwrap().can_sleep = false;
+        for _ in 0..300 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(!world.physics(ball).unwrap().is_sleeping());
+    }
+
+    #[test]
+    fn removing_a_support_wakes_what_rests_on_it() {
+        let mut world = World::default();
+        let support = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.0), 0.0),
+            Shape::Rect { w: 4.0, h: 1.0 },
+        );
+        let shape = Shape::Rect { w: 1.0, h: 1.0 };
+        let resting = world.add_body(
+            Physics::new(Vec2::new(0.0, -1.0), 1.0, shape.rotational_inertia(), 0.0),
+            shape,
+        );
+        for _ in 0..300 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(world.physics(resting).unwrap().is_sleeping());
+
+        world.remove_body(support);
+        let y = world.physics(resting).unwrap().position.y;
+        world.step(1.0 / 60.0);
+        let physics = world.physics(resting).unwrap();
+        assert!(!physics.is_sleeping());
+        assert!(physics.velocity.y > 0.0 && physics.position.y > y);
+    }
+
+    #[test]
+    fn raycasts_and_queries() {
+        let mut world = World::default();
+        let (p, s) = ball(3.0, 0.0);
+        let near = world.add_body(p, s);
+        let shape = Shape::Rect { w: 1.0, h: 2.0 };
+        let mut box_physics = Physics::new_static(Vec2::new(6.0, 0.0), 0.0);
+        box_physics.rotation = crate::vec2::consts::FRAC_PI_2;
+        let far = world.add_body(box_physics, shape);
+
+        let hit = world
+            .raycast(
+                Vec2::new(0.0, 0.0),
+                Vec2::new(2.0, 0.0),
+                10.0,
+                &Filter::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.body, near);
+        assert!(hit.point.nearly_eq(Vec2::new(2.5, 0.0)));
+        assert!(hit.normal.nearly_eq(Vec2::new(-1.0, 0.0)));
+        assert!((hit.fraction - 0.25).abs() < 0.001);
+
+        // Turned on its side, the box is 2 wide.
+        let hits = world.raycast_all(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            10.0,
+            &Filter::default(),
+        );
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].body, far);
+        assert!(hits[1].point.nearly_eq(Vec2::new(5.0, 0.0)));
+        assert!(hits[1].normal.nearly_eq(Vec2::new(-1.0, 0.0)));
+        assert!(world
+            .raycast(
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                2.0,
+                &Filter::default()
+            )
+            .is_none());
+        // Starting inside a body, only the other one is hit.
+        let hit = world
+            .raycast(
+                Vec2::new(3.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                10.0,
+                &Filter::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.body, far);
+
+        assert_eq!(
+            world.query_point(Vec2::new(6.9, 0.4), &Filter::default()),
+            vec![far]
+        );
+        assert!(world
+            .query_point(Vec2::new(6.9, 0.6), &Filter::default())
+            .is_empty());
+        let area = Aabb::new(Vec2::new(2.0, -1.0), Vec2::new(5.5, 1.0));
+        assert_eq!(world.query_aabb(&area, &Filter::default()), vec![near, far]);
+
+        // Filtered out bodies are looked through.
+        world.physics_mut(near).unwrap().filter.category = 2;
+        let solid = Filter {
+            mask: !2,
+            ..Filter::default()
+        };
+        let hit = world
+            .raycast(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 10.0, &solid)
+            .unwrap();
+        assert_eq!(hit.body, far);
+        assert_eq!(world.query_aabb(&area, &solid), vec![far]);
+
+        // Moved bodies are found where they are now.
+        world.physics_mut(near).unwrap().position = Vec2::new(0.0, 5.0);
+        assert_eq!(
+            world.query_point(Vec2::new(0.0, 5.0), &Filter::default()),
+            vec![near]
+        );
+        assert_eq!(world.query_aabb(&area, &Filter::default()), vec![far]);
+    }
+
+    #[test]
+    fn shape_cast_stops_at_first_body() {
+        let mut world = World::default();
+        let wall = world.add_body(
+            Physics::new_static(Vec2::new(5.0, 0.0), 0.0),
+            Shape::Rect { w: 1.0, h: 4.0 },
+        );
+        let probe = Shape::Rect { w: 1.0, h: 1.0 };
+
+        let hit = world
+            .shape_cast(
+                &probe,
+                Vec2::new(0.0, 0.0),
+                0.0,
+                Vec2::new(10.0, 0.0),
+                &Filter::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.body, wall);
+        // Touches at x = 4, 4 out of 10 units in.
+        assert!((hit.fraction - 0.4).abs() < 0.01);
+        assert!(hit.normal.nearly_eq(Vec2::new(-1.0, 0.0)));
+        assert!((hit.point.x - 4.5).abs() < 0.01);
+        assert!((hit.position.x - 4.0).abs() < 0.01 && hit.position.y == 0.0);
+
+        let above = world.shape_cast(
+            &probe,
+            Vec2::new(0.0, -3.0),
+            0.0,
+            Vec2::new(10.0, 0.0),
+            &Filter::default(),
+        );
+        assert!(above.is_none());
+    }
+
+    #[test]
+    fn filtered_bodies_pass_through() {
+        let mut world = World::default();
+        let ground = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 20.0, h: 1.0 },
+        );
+        let drop = |world: &mut World, x: Real, group: i16| {
+            let (mut p, s) = ball(x, -0.5);
+            p.filter.group = group;
+            let below = world.add_body(p, s);
+            let (mut p, s) = ball(x, -1.6);
+            p.filter.group = group;
+            (below, world.add_body(p, s))
+        };
+        let (_, stacked) = drop(&mut world, -5.0, 0);
+        let (_, ghost) = drop(&mut world, 0.0, -1);
+        let (_, vetoed) = drop(&mut world, 5.0, 0);
+        world.set_contact_filter(move |a, _, b, _| {
+            (a != vetoed && b != vetoed) || a == ground || b == ground
+        });
+
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+
+        let y = |body| world.physics(body).unwrap().position.y;
+        assert!((y(stacked) - -1.5).abs() < 0.05);
+        // Resting on the ground, inside the ball below.
+        assert!((y(ghost) - -0.5).abs() < 0.05);
+        assert!((y(vetoed) - -0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn contact_events() {
+        let mut world = World::default();
+        let ground = world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let mut zone = Physics::new_static(Vec2::new(0.0, -3.0), 0.0);
+        zone.sensor = true;
+        let zone = world.add_body(zone, Shape::Rect { w: 4.0, h: 1.0 });
+        let (p, s) = ball(0.0, -5.0);
+        let ball = world.add_body(p, s);
+
+        let mut phases = Vec::new();
+        let mut landing = None;
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+            for event in world.contact_events() {
+                if event.contact.a == zone || event.contact.b == zone {
+                    assert!(event.contact.sensor);
+                    if phases.last() != Some(&event.phase) {
+                        phases.push(event.phase);
+                    }
+                } else if event.phase == ContactPhase::Begin {
+                    assert_eq!((event.contact.a, event.contact.b), (ground, ball));
+                    landing = Some(event.contact.normal_impulse);
+                }
+            }
+        }
+
+        // Passed through the sensor without slowing down.
+        use ContactPhase::*;
+        assert_eq!(phases, vec![Begin, Persist, End]);
+        // Fell 4.5 m, so hit the ground at about 9.4 m/s and stopped dead.
+        let impulse = landing.unwrap();
+        assert!(impulse > 8.5 && impulse < 10.0);
+        assert!(world.contacts().iter().all(|c| !c.sensor));
+    }
+
+    #[test]
+    fn one_way_platform() {
+        let mut world = World::default();
+        // Unrelated to passing through, see one_way_tolerance.
+        world.solver.restitution_threshold = 100.0;
+        let mut platform = Physics::new_static(Vec2::new(0.0, 0.0), 0.0);
+        platform.one_way = Some(Vec2::new(0.0, -1.0));
+        let platform = world.add_body(platform, Shape::Rect { w: 4.0, h: 0.2 });
+        let (mut p, s) = ball(0.0, 1.0);
+        p.velocity = Vec2::new(0.0, -8.0);
+        let ball = world.add_body(p, s);
+
+        // Jumps up through it, then lands on top.
+        let mut highest = Real::MAX;
+        for _ in 0..180 {
+            world.step(1.0 / 60.0);
+            highest = highest.min(world.physics(ball).unwrap().position.y);
+        }
+        assert!(highest < -2.0);
+        let y = world.physics(ball).unwrap().position.y;
+        assert!((y - -0.6).abs() < 0.02);
+        assert_eq!(world.contacts_with(platform).count(), 1);
+
+        world.disable_contact(ball, platform);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+        assert!(world.physics(ball).unwrap().position.y > 1.0);
+    }
+
+    #[test]
+    fn dumbbell_rests_on_both_ends() {
+        use crate::shape::ChildShape;
+
+        let mut world = World::default();
+        world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.5), 0.0),
+            Shape::Rect { w: 10.0, h: 1.0 },
+        );
+        let weight = |x: Real| ChildShape {
+            offset: Vec2::new(x, 0.0),
+            rotation: 0.0,
+            shape: Shape::Circle { radius: 0.5 },
+        };
+        let bar = ChildShape {
+            offset: Vec2::new(1.0, 0.0),
+            rotation: 0.0,
+            shape: Shape::Rect { w: 2.0, h: 0.2 },
+        };
+        let (shape, center) = Shape::compound(vec![weight(0.0), bar, weight(2.0)]);
+        assert!(center.nearly_eq(Vec2::new(1.0, 0.0)));
+        let physics = Physics::new(Vec2::new(0.0, -2.0), 2.0, shape.rotational_inertia(), 0.0);
+        let dumbbell = world.add_body(physics, shape);
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+
+        let physics = world.physics(dumbbell).unwrap();
+        assert!((physics.position.y - -0.5).abs() < 0.02);
+        assert!(physics.rotation.abs() < 0.01);
+        assert_eq!(world.contacts_with(dumbbell).count(), 2);
+        let hit = world
+            .raycast(
+                Vec2::new(1.0, -3.0),
+                Vec2::new(0.0, 1.0),
+                5.0,
+                &Filter::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.body, dumbbell);
+        assert!((hit.point.y - -1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn capsule_slides_over_chain_seams() {
+        let mut world = World::new(Vec2::new(0.0, 9.81));
+        let points: Vec<Vec2> = (0..=20).map(|i| Vec2::new(i as Real - 2.0, 0.0)).collect();
+        world.add_body(
+            Physics::new_static(Vec2::new(0.0, 0.0), 0.0),
+            Shape::chain(&points),
+        );
+        let shape = Shape::Capsule {
+            length: 0.5,
+            radius: 0.25,
+        };
+        let mut physics = Physics::new(Vec2::new(0.0, -0.5), 1.0, 0.0, 0.0);
+        physics.inverse_angular_mass = 0.0;
+        physics.static_friction = 0.0;
+        physics.dynamic_friction = 0.0;
+        physics.velocity = Vec2::new(5.0, 0.0);
+        let capsule = world.add_body(physics, shape);
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+
+        // Two seconds at 5 m/s without catching on the seams between the segments.
+        let physics = world.physics(capsule).unwrap();
+        assert!((physics.velocity.x - 5.0).abs() < 0.05);
+        assert!((physics.position.x - 10.0).abs() < 0.1);
+        assert!((physics.position.y - -0.5).abs() < 0.02);
+
+        let hit = world
+            .raycast(
+                Vec2::new(3.5, -3.0),
+                Vec2::new(0.0, 1.0),
+                5.0,
+                &Filter::default(),
+            )
+            .unwrap();
+        assert!(hit.point.nearly_eq(Vec2::new(3.5, 0.0)));
+        assert!(hit.normal.nearly_eq(Vec2::new(0.0, -1.0)));
+    }
+}
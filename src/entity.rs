@@ -1,20 +1,22 @@
+use catphys::BodyHandle;
 use std::collections::BTreeMap;
 
 #[derive(Ord, Eq, PartialOrd, PartialEq)]
 pub enum Component {
-    Physics,
-    Shape,
     Render,
 }
 
 pub struct Entity {
     component_idx: BTreeMap<Component, usize>,
+    // Physics and shape live in the physics world.
+    pub body: Option<BodyHandle>,
     pub colliding: bool,
 }
 
 #[derive(Default)]
 pub struct EntityBuilder {
     component_idx: BTreeMap<Component, usize>,
+    body: Option<BodyHandle>,
 }
 
 impl Entity {
@@ -28,11 +30,12 @@ impl EntityBuilder {
     pub fn new() -> Self {
         Self {
             component_idx: BTreeMap::<Component, usize>::default(),
+            body: None,
         }
     }
 
-    pub fn with_physics_component(mut self, idx: usize) -> Self {
-        self.component_idx.insert(Component::Physics, idx);
+    pub fn with_body(mut self, body: BodyHandle) -> Self {
+        self.body = Some(body);
         self
     }
 
@@ -41,14 +44,10 @@ impl EntityBuilder {
         self
     }
 
-    pub fn with_shape_component(mut self, idx: usize) -> Self {
-        self.component_idx.insert(Component::Shape, idx);
-        self
-    }
-
     pub fn build(self) -> Entity {
         Entity {
             component_idx: self.component_idx,
+            body: self.body,
             colliding: false,
         }
     }
@@ -56,12 +55,16 @@ impl EntityBuilder {
 
 #[test]
 fn entity_builder_test() {
+    let mut world = catphys::World::default();
+    let body = world.add_body(
+        catphys::Physics::new(catphys::Vec2::new(0.0, 0.0), 1.0, 1.0, 0.0),
+        catphys::Shape::Circle { radius: 1.0 },
+    );
     let entity = EntityBuilder::new()
-        .with_physics_component(123)
+        .with_body(body)
         .with_render_component(456)
-        .with_shape_component(123)
         .build();
-    assert_eq!(123, entity.get_index_for(Component::Physics).unwrap());
+    assert_eq!(Some(body), entity.body);
     assert_eq!(456, entity.get_index_for(Component::Render).unwrap());
-    assert_eq!(123, entity.get_index_for(Component::Shape).unwrap());
+    assert!(EntityBuilder::new().build().body.is_none());
 }
@@ -1,4 +1,4 @@
-use catphys::Vec2;
+use catphys::{Real, Transform2, Vec2};
 use sdl2::pixels::Color;
 use sdl2::rect::Point;
 use sdl2::rect::Rect;
@@ -122,16 +122,12 @@ impl Graphics {
         }
     }
 
-    pub fn draw_box(&mut self, origin: Vec2, width: f32, height: f32, rotation: f32) {
-        let mut v0 = Vec2::new(width * -0.5, height * -0.5).rotate(rotation);
-        let mut v1 = Vec2::new(width * 0.5, height * -0.5).rotate(rotation);
-        let mut v2 = Vec2::new(width * 0.5, height * 0.5).rotate(rotation);
-        let mut v3 = Vec2::new(width * -0.5, height * 0.5).rotate(rotation);
-
-        v0 += origin;
-        v1 += origin;
-        v2 += origin;
-        v3 += origin;
+    pub fn draw_box(&mut self, origin: Vec2, width: Real, height: Real, rotation: Real) {
+        let transform = Transform2::new(origin, rotation);
+        let v0 = transform.apply(Vec2::new(width * -0.5, height * -0.5));
+        let v1 = transform.apply(Vec2::new(width * 0.5, height * -0.5));
+        let v2 = transform.apply(Vec2::new(width * 0.5, height * 0.5));
+        let v3 = transform.apply(Vec2::new(width * -0.5, height * 0.5));
 
         // TODO: Draw filled box!
         // NOTE: Canvas already has functions for drawing primitives.
@@ -141,7 +137,13 @@ impl Graphics {
         self.draw_line(v3.x as i32, v3.y as i32, v0.x as i32, v0.y as i32);
     }
 
-    pub fn draw_texture(&mut self, texture: &Texture, dest: Rect, flip: bool, rotation: f32) {
+    pub fn draw_texture(
+        &mut self,
+        texture: &Texture,
+        dest: Rect,
+        flip: bool,
+        rotation: impl Into<f64>,
+    ) {
         self.canvas
             .copy_ex(
                 texture,
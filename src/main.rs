@@ -19,7 +19,7 @@ use crate::graphics::Graphics;
 use crate::input::Input;
 use crate::system::System;
 use crate::world::{World, PIXELS_PER_METER};
-use catphys::{Shape, Vec2};
+use catphys::{Real, Shape, Vec2};
 
 const WIDTH: u32 = 1200u32;
 const HEIGHT: u32 = 800u32;
@@ -27,8 +27,8 @@ const MARGIN: u32 = 25u32;
 
 fn make_world() -> World {
     World::new(
-        Vec2::new(MARGIN as f32, MARGIN as f32),
-        Vec2::new((WIDTH - MARGIN) as f32, (HEIGHT - MARGIN) as f32),
+        Vec2::new(MARGIN as Real, MARGIN as Real),
+        Vec2::new((WIDTH - MARGIN) as Real, (HEIGHT - MARGIN) as Real),
     )
 }
 
@@ -53,56 +53,75 @@ fn update_world(
         world.spawn_player(((WIDTH / 2) as i32, (HEIGHT / 2) as i32), 0.5, 0.5, 2.0);
     }
 
-    if input.key_pressed(Scancode::Space) && !input.key_was_pressed(Scancode::Space) {
-        // TODO: Currently the thing basically flies if you keep pressing space.
+    if input.key_pressed(Scancode::C) && !input.key_was_pressed(Scancode::C) {
+        world.spawn_chain(input.mouse_position(), 12);
+    }
+
+    if input.key_pressed(Scancode::L) && !input.key_was_pressed(Scancode::L) {
+        world.spawn_l_block(input.mouse_position(), 0.6);
+    }
+
+    if input.key_pressed(Scancode::Space)
+        && !input.key_was_pressed(Scancode::Space)
+        && world.player_on_ground()
+    {
         if let Some(player_idx) = world.player_entity_idx {
-            let player_phys_idx = world.entities[player_idx].get_index_for(Component::Physics);
-            if let Some(idx) = player_phys_idx {
-                world.physics_components[idx].position.y -= 0.5 * PIXELS_PER_METER;
+            if let Some(physics) = world.entity_physics_mut(player_idx) {
+                physics.position.y -= 0.5 * PIXELS_PER_METER;
             }
         }
     }
 
+    if input.key_pressed(Scancode::Down) && !input.key_was_pressed(Scancode::Down) {
+        world.drop_player_through();
+    }
+
     if input.key_pressed(Scancode::Right) {
         if let Some(player_idx) = world.player_entity_idx {
-            let player_phys_idx = world.entities[player_idx].get_index_for(Component::Physics);
-            if let Some(idx) = player_phys_idx {
+            if let Some(physics) = world.entity_physics_mut(player_idx) {
+                physics.apply_impulse(Vec2::new(0.08 * PIXELS_PER_METER, 0.0));
                 world.flip_player_texture = false;
-                world.physics_components[idx]
-                    .apply_impulse(Vec2::new(0.08 * PIXELS_PER_METER, 0.0));
             }
         }
     }
 
     if input.key_pressed(Scancode::Left) {
         if let Some(player_idx) = world.player_entity_idx {
-            let player_phys_idx = world.entities[player_idx].get_index_for(Component::Physics);
-            if let Some(idx) = player_phys_idx {
+            if let Some(physics) = world.entity_physics_mut(player_idx) {
+                physics.apply_impulse(Vec2::new(-0.08 * PIXELS_PER_METER, 0.0));
                 world.flip_player_texture = true;
-                world.physics_components[idx]
-                    .apply_impulse(Vec2::new(-0.08 * PIXELS_PER_METER, 0.0));
             }
         }
     }
 
+    let (mouse_x, mouse_y) = input.mouse_position();
+    let mouse = Vec2::new(mouse_x as Real, mouse_y as Real);
     if input.mouse_pressed(MouseButton::Right) && !input.mouse_was_pressed(MouseButton::Right) {
-        world.spawn_ball(input.mouse_position(), 0.05, 50.0);
+        world.spawn_ball(input.mouse_position(), 0.05);
     } else if input.mouse_pressed(MouseButton::Left) && !input.mouse_was_pressed(MouseButton::Left)
     {
-        world.spawn_ball(input.mouse_position(), 0.4, 400.0);
+        if !world.pick(mouse) {
+            world.spawn_ball(input.mouse_position(), 0.4);
+        }
+    } else if input.mouse_pressed(MouseButton::Left) {
+        world.drag_picked(mouse);
+    } else {
+        world.picked = None;
     }
 
-    world.update_physics(delta_time_secs);
+    world.update_physics(Real::from(delta_time_secs));
 
     gfx.begin_frame();
+    let alpha = world.timestep.alpha();
     for entity in world.entities.iter() {
         if let Some(idx) = entity.get_index_for(Component::Render) {
             let color = world.render_components[idx].color;
-            if let Some(idx) = entity.get_index_for(Component::Physics) {
-                let pos = world.physics_components[idx].position;
-                let rotation = world.physics_components[idx].rotation;
-                if let Some(idx) = entity.get_index_for(Component::Shape) {
-                    let shape = &world.shape_components[idx];
+            if let Some(body) = entity.body {
+                if let (Some(physics), Some(shape)) =
+                    (world.physics.physics(body), world.physics.shape(body))
+                {
+                    let pos = physics.interpolated_position(alpha);
+                    let rotation = physics.interpolated_rotation(alpha);
                     if entity.colliding {
                         gfx.set_draw_color(Color::RGB(99, 125, 10));
                     } else {
@@ -112,6 +131,10 @@ fn update_world(
                         Shape::Circle { radius } => {
                             gfx.draw_circle((pos.x as i32, pos.y as i32), *radius as i32)
                         }
+                        // Only the cat is a moving box, the rest are platforms.
+                        Shape::Rect { w, h } if !physics.is_dynamic() => {
+                            gfx.draw_box(pos, *w, *h, rotation)
+                        }
                         Shape::Rect { w, h } => gfx.draw_texture(
                             texture,
                             Rect::new(
@@ -123,7 +146,7 @@ fn update_world(
                             world.flip_player_texture,
                             rotation,
                         ),
-                        _ => (),
+                        _ => draw_parts(gfx, shape, pos, rotation),
                     }
                 }
             }
@@ -136,6 +159,34 @@ fn update_world(
     still_running
 }
 
+// Outlines of the shapes without a texture.
+fn draw_parts(gfx: &mut Graphics, shape: &Shape, pos: Vec2, rotation: Real) {
+    for (part, pos, rotation) in shape.parts_at(pos, rotation) {
+        match part {
+            Shape::Circle { radius } => {
+                gfx.draw_circle((pos.x as i32, pos.y as i32), *radius as i32)
+            }
+            Shape::Rect { w, h } => gfx.draw_box(pos, *w, *h, rotation),
+            Shape::Capsule { length, radius } => {
+                gfx.draw_box(pos, radius * 2.0, *length, rotation);
+                let end = Vec2::new(0.0, length * 0.5).rotate(rotation);
+                for end in [pos + end, pos - end] {
+                    gfx.draw_circle((end.x as i32, end.y as i32), *radius as i32);
+                }
+            }
+            Shape::Segment { .. } | Shape::Polygon { .. } => {
+                let verts = part.vertices_at(pos, rotation);
+                // A segment is drawn there and back.
+                for (i, a) in verts.iter().enumerate() {
+                    let b = verts[(i + 1) % verts.len()];
+                    gfx.draw_line(a.x as i32, a.y as i32, b.x as i32, b.y as i32);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let system = System::init("fonts/WorkSans-Regular.ttf".to_string())?;
     let mut graphics = system.init_graphics(WIDTH, HEIGHT, false)?;
@@ -1,49 +1,147 @@
-use crate::entity::{Component, Entity, EntityBuilder};
+use crate::entity::{Entity, EntityBuilder};
 use crate::graphics::Render;
-use catphys::{Collision, Force, Physics, Shape, Vec2};
+use catphys::vec2::consts;
+use catphys::{
+    BodyHandle, ChildShape, Drag, Filter, FixedTimestep, ForceTarget, Joint, JointKind, Physics,
+    Real, Shape, Vec2,
+};
 use sdl2::pixels::Color;
+use std::collections::HashSet;
 
-pub const PIXELS_PER_METER: f32 = 100.0;
+pub const PIXELS_PER_METER: Real = 100.0;
+// Densities in kg per square meter, for Physics::from_shape in the pixel World.
+const BALL_DENSITY: Real = 800.0 / (PIXELS_PER_METER * PIXELS_PER_METER);
+const BLOCK_DENSITY: Real = 50.0 / (PIXELS_PER_METER * PIXELS_PER_METER);
+// Small balls are decoration and pass through each other.
+const PARTICLE_GROUP: i16 = -1;
+// Their own category so queries can skip them.
+const PARTICLE_CATEGORY: u16 = 2;
 
 pub struct World {
-    upper_left: Vec2,
-    lower_right: Vec2,
-
-    // Environment and world forces.
-    gravitational_acceleration: Vec2,
+    // Bodies, gravity and collisions.
+    pub physics: catphys::World,
+    pub timestep: FixedTimestep,
 
     // Entity-component.
-    pub physics_components: Vec<Physics>,
-    pub shape_components: Vec<Shape>,
     pub render_components: Vec<Render>,
     pub entities: Vec<Entity>,
     pub player_entity_idx: Option<usize>,
     pub flip_player_texture: bool,
+    pub picked: Option<BodyHandle>, // Dragged around by the mouse.
 }
 
 impl World {
     pub fn new(ul: Vec2, lr: Vec2) -> Self {
-        Self {
-            upper_left: ul,
-            lower_right: lr,
-            gravitational_acceleration: Vec2::new(0.0, 9.81) * PIXELS_PER_METER,
-            physics_components: Vec::<Physics>::default(),
-            shape_components: Vec::<Shape>::default(),
+        let mut physics = catphys::World::new(Vec2::new(0.0, 9.81) * PIXELS_PER_METER);
+        // The solver tolerances are in meters, the world is in pixels.
+        physics.solver.slop *= PIXELS_PER_METER;
+        physics.solver.max_correction *= PIXELS_PER_METER;
+        physics.solver.restitution_threshold *= PIXELS_PER_METER;
+        physics.one_way_tolerance *= PIXELS_PER_METER;
+        physics.sleep.linear_velocity *= PIXELS_PER_METER;
+        physics
+            .forces
+            .add(ForceTarget::All, Drag { k1: 0.65, k2: 0.0 });
+        add_boundaries(&mut physics, ul, lr);
+
+        let mut world = Self {
+            physics,
+            timestep: FixedTimestep::new(120.0, 8),
             render_components: Vec::<Render>::default(),
             entities: Vec::<Entity>::default(),
             player_entity_idx: None,
             flip_player_texture: false,
+            picked: None,
+        };
+        // Two ledges to jump up through.
+        let (w, h) = (lr.x - ul.x, lr.y - ul.y);
+        world.spawn_platform(Vec2::new(ul.x + w * 0.25, ul.y + h * 0.7), 2.5);
+        world.spawn_platform(Vec2::new(ul.x + w * 0.75, ul.y + h * 0.45), 2.5);
+        world.spawn_ramp(lr, 1.5); // Quarter pipe in the lower right corner.
+        world.spawn_terrain(&[
+            Vec2::new(ul.x, lr.y),
+            Vec2::new(ul.x, lr.y - 0.8 * PIXELS_PER_METER),
+            Vec2::new(ul.x + 0.6 * PIXELS_PER_METER, lr.y - 0.3 * PIXELS_PER_METER),
+            Vec2::new(ul.x + 1.2 * PIXELS_PER_METER, lr.y - 0.5 * PIXELS_PER_METER),
+            Vec2::new(ul.x + 2.0 * PIXELS_PER_METER, lr.y),
+        ]);
+        world
+    }
+
+    // Physics of the entity's body, if it has one.
+    pub fn entity_physics_mut(&mut self, entity_idx: usize) -> Option<&mut Physics> {
+        let body = self.entities.get(entity_idx)?.body?;
+        self.physics.physics_mut(body)
+    }
+
+    // Something right below the player to jump off.
+    pub fn player_on_ground(&self) -> bool {
+        let Some(body) = self
+            .player_entity_idx
+            .and_then(|idx| self.entities.get(idx)?.body)
+        else {
+            return false;
+        };
+        let (Some(physics), Some(Shape::Rect { h, .. })) =
+            (self.physics.physics(body), self.physics.shape(body))
+        else {
+            return false;
+        };
+        // Starts inside the player, so the player itself isn't hit. Particles are no
+        // ground to stand on.
+        let solid = Filter {
+            mask: !PARTICLE_CATEGORY,
+            ..Filter::default()
+        };
+        self.physics
+            .raycast(
+                physics.position,
+                Vec2::new(0.0, 1.0),
+                h * 0.5 + 0.05 * PIXELS_PER_METER,
+                &solid,
+            )
+            .is_some()
+    }
+
+    // Lets the player fall through the one-way platforms it's standing on.
+    pub fn drop_player_through(&mut self) {
+        let Some(player) = self
+            .player_entity_idx
+            .and_then(|idx| self.entities.get(idx)?.body)
+        else {
+            return;
+        };
+        let platforms: Vec<BodyHandle> = self
+            .physics
+            .contacts_with(player)
+            .map(|c| if c.a == player { c.b } else { c.a })
+            .filter(|b| {
+                self.physics
+                    .physics(*b)
+                    .is_some_and(|p| p.one_way.is_some())
+            })
+            .collect();
+        for platform in platforms {
+            self.physics.disable_contact(player, platform);
         }
     }
 
-    pub fn add_shape(&mut self, shape: Shape) -> usize {
-        self.shape_components.push(shape);
-        self.shape_components.len() - 1
+    // Grabs the dynamic body under the point, if there is one.
+    pub fn pick(&mut self, point: Vec2) -> bool {
+        let physics = &self.physics;
+        self.picked = physics
+            .query_point(point, &Filter::default())
+            .into_iter()
+            .find(|b| physics.physics(*b).is_some_and(|p| p.is_dynamic()));
+        self.picked.is_some()
     }
 
-    pub fn add_physics(&mut self, phys: Physics) -> usize {
-        self.physics_components.push(phys);
-        self.physics_components.len() - 1
+    // Pulls the picked body towards the point.
+    pub fn drag_picked(&mut self, point: Vec2) {
+        if let Some(physics) = self.picked.and_then(|b| self.physics.physics_mut(b)) {
+            physics.wake();
+            physics.velocity = (point - physics.position) * 10.0;
+        }
     }
 
     pub fn add_render(&mut self, rend: Render) -> usize {
@@ -56,33 +154,182 @@ impl World {
         self.entities.len() - 1
     }
 
-    pub fn spawn_ball(&mut self, pos: (i32, i32), rad: f32, mass: f32) {
+    // Radius in meters, heavier the bigger it is. Nothing is spawned without a radius.
+    pub fn spawn_ball(&mut self, pos: (i32, i32), rad: Real) {
+        let ball = Shape::Circle {
+            radius: rad * PIXELS_PER_METER,
+        };
+        let Some(mut physics) = Physics::from_shape(
+            Vec2::new(pos.0 as Real, pos.1 as Real),
+            &ball,
+            BALL_DENSITY,
+            0.5,
+        ) else {
+            return;
+        };
         let render = Render {
             color: Color::RGB(255, 255, 255),
         };
         //TODO: Only need one render component
         // if the balls all look the same.
         let rend_idx = self.add_render(render);
-        let ball = Shape::Circle {
-            radius: rad * PIXELS_PER_METER,
+        // Small balls move far compared to their size in one step.
+        if rad < 0.1 {
+            physics.bullet = true;
+            physics.filter.group = PARTICLE_GROUP;
+            physics.filter.category = PARTICLE_CATEGORY;
+        }
+        let body = self.physics.add_body(physics, ball);
+        self.add_entity(
+            EntityBuilder::default()
+                .with_body(body)
+                .with_render_component(rend_idx)
+                .build(),
+        );
+    }
+
+    // L shaped rigid body made of two boxes, size is the length of the legs in meters.
+    pub fn spawn_l_block(&mut self, pos: (i32, i32), size: Real) {
+        let rend_idx = self.add_render(Render {
+            color: Color::RGB(120, 160, 220),
+        });
+        let (long, thick) = (size * PIXELS_PER_METER, size * 0.3 * PIXELS_PER_METER);
+        let (shape, center) = Shape::compound(vec![
+            ChildShape {
+                offset: Vec2::new(0.0, 0.0),
+                rotation: 0.0,
+                shape: Shape::Rect { w: thick, h: long },
+            },
+            ChildShape {
+                offset: Vec2::new((long + thick) * 0.5, (long - thick) * 0.5),
+                rotation: 0.0,
+                shape: Shape::Rect { w: long, h: thick },
+            },
+        ]);
+        let body = self.physics.add_body(
+            Physics::from_shape(
+                Vec2::new(pos.0 as Real, pos.1 as Real) + center,
+                &shape,
+                BLOCK_DENSITY,
+                0.2,
+            )
+            .unwrap(),
+            shape,
+        );
+        self.add_entity(
+            EntityBuilder::default()
+                .with_body(body)
+                .with_render_component(rend_idx)
+                .build(),
+        );
+    }
+
+    // Static ledge that can be jumped onto from below, width in meters.
+    pub fn spawn_platform(&mut self, pos: Vec2, width: Real) {
+        let rend_idx = self.add_render(Render {
+            color: Color::RGB(160, 120, 80),
+        });
+        let mut physics = Physics::new_static(pos, 0.0);
+        physics.one_way = Some(Vec2::new(0.0, -1.0));
+        let body = self.physics.add_body(
+            physics,
+            Shape::Rect {
+                w: width * PIXELS_PER_METER,
+                h: 0.1 * PIXELS_PER_METER,
+            },
+        );
+        self.add_entity(
+            EntityBuilder::default()
+                .with_body(body)
+                .with_render_component(rend_idx)
+                .build(),
+        );
+    }
+
+    // Static quarter pipe into the corner, made of a chain of segments, radius in meters.
+    pub fn spawn_ramp(&mut self, corner: Vec2, radius: Real) {
+        let rend_idx = self.add_render(Render {
+            color: Color::RGB(160, 120, 80),
+        });
+        let radius = radius * PIXELS_PER_METER;
+        let points: Vec<Vec2> = (0..=12)
+            .map(|i| {
+                let angle = consts::FRAC_PI_2 * i as Real / 12.0;
+                Vec2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+        // The body sits at the center of the curve, from the wall down to the floor.
+        let center = corner - Vec2::new(radius, radius);
+        let body = self
+            .physics
+            .add_body(Physics::new_static(center, 0.0), Shape::chain(&points));
+        self.add_entity(
+            EntityBuilder::default()
+                .with_body(body)
+                .with_render_component(rend_idx)
+                .build(),
+        );
+    }
+
+    // Static ground from an outline in World coordinates, it doesn't have to be convex.
+    pub fn spawn_terrain(&mut self, outline: &[Vec2]) {
+        let Some(shape) = Shape::decomposed(outline) else {
+            return;
         };
-        let phys_idx = self.add_physics(Physics::new(
-            Vec2::new(pos.0 as f32, pos.1 as f32),
-            mass,
-            ball.rotational_inertia(),
-            0.5,
-        ));
-        let shape_idx = self.add_shape(ball);
+        let rend_idx = self.add_render(Render {
+            color: Color::RGB(160, 120, 80),
+        });
+        let body = self
+            .physics
+            .add_body(Physics::new_static(Vec2::new(0.0, 0.0), 0.0), shape);
         self.add_entity(
             EntityBuilder::default()
-                .with_shape_component(shape_idx)
-                .with_physics_component(phys_idx)
+                .with_body(body)
                 .with_render_component(rend_idx)
                 .build(),
         );
     }
 
-    pub fn spawn_player(&mut self, pos: (i32, i32), width: f32, height: f32, mass: f32) {
+    // Balls hanging from a fixed point, each one held at a distance from the one above.
+    pub fn spawn_chain(&mut self, pos: (i32, i32), links: usize) {
+        let rend_idx = self.add_render(Render {
+            color: Color::RGB(200, 200, 200),
+        });
+        let radius = 0.1 * PIXELS_PER_METER;
+        let spacing = 0.25 * PIXELS_PER_METER;
+
+        let spawn_link = |world: &mut World, physics: Physics| {
+            let body = world.physics.add_body(physics, Shape::Circle { radius });
+            world.add_entity(
+                EntityBuilder::default()
+                    .with_body(body)
+                    .with_render_component(rend_idx)
+                    .build(),
+            );
+            body
+        };
+
+        let top = Vec2::new(pos.0 as Real, pos.1 as Real);
+        let mut previous = spawn_link(self, Physics::new_static(top, 0.0));
+        for i in 1..=links {
+            let position = top + Vec2::new(0.0, spacing * i as Real);
+            let inertia = Shape::Circle { radius }.rotational_inertia();
+            let link = spawn_link(self, Physics::new(position, 5.0, inertia, 0.0));
+            self.physics.add_joint(Joint::new(
+                previous,
+                link,
+                JointKind::Distance {
+                    local_a: Vec2::new(0.0, 0.0),
+                    local_b: Vec2::new(0.0, 0.0),
+                    length: spacing,
+                    rope: false,
+                },
+            ));
+            previous = link;
+        }
+    }
+
+    pub fn spawn_player(&mut self, pos: (i32, i32), width: Real, height: Real, mass: Real) {
         let render = Render {
             color: Color::RGB(0, 255, 0),
         };
@@ -91,103 +338,76 @@ impl World {
             w: width * PIXELS_PER_METER,
             h: height * PIXELS_PER_METER,
         };
-        let phys_idx = self.add_physics(Physics::new(
-            Vec2::new(pos.0 as f32, pos.1 as f32),
-            mass,
-            rect.rotational_inertia(),
-            0.2,
-        ));
-        let shape_idx = self.add_shape(rect);
+        let body = self.physics.add_body(
+            Physics::new(
+                Vec2::new(pos.0 as Real, pos.1 as Real),
+                mass,
+                rect.rotational_inertia(),
+                0.2,
+            ),
+            rect,
+        );
+        // Keep the cat upright, contacts can't turn it. It's moved directly by the keys,
+        // so it mustn't fall asleep.
+        if let Some(physics) = self.physics.physics_mut(body) {
+            physics.inverse_angular_mass = 0.0;
+            physics.can_sleep = false;
+        }
         self.player_entity_idx = Some(
             self.add_entity(
                 EntityBuilder::default()
-                    .with_shape_component(shape_idx)
-                    .with_physics_component(phys_idx)
+                    .with_body(body)
                     .with_render_component(rend_idx)
                     .build(),
             ),
         );
     }
 
-    pub fn update_physics(&mut self, delta_time_seconds: f32) {
-        self.physics_components.iter_mut().for_each(|physics| {
-            let weight = self.gravitational_acceleration * physics.mass;
-            physics.apply_force(weight);
-            //physics.apply_torque(0.01);
-            //physics.apply_force(Force::drag(0.001, physics.velocity));
-            physics.apply_force(Force::friction(0.65, physics.velocity));
-            physics.integrate(delta_time_seconds);
-            physics.integrate_angular(delta_time_seconds);
-        });
+    // Runs as many fixed steps as fit in the frame time.
+    pub fn update_physics(&mut self, delta_time_seconds: Real) {
+        for _ in 0..self.timestep.advance(delta_time_seconds) {
+            self.step_physics(self.timestep.dt());
+        }
+    }
 
+    fn step_physics(&mut self, delta_time_seconds: Real) {
+        self.physics.step(delta_time_seconds);
+
+        let colliding: HashSet<BodyHandle> = self
+            .physics
+            .contacts()
+            .iter()
+            .filter(|c| !c.sensor)
+            .flat_map(|c| [c.a, c.b])
+            .collect();
         for entity in &mut self.entities {
-            entity.colliding = false;
+            entity.colliding = entity.body.is_some_and(|b| colliding.contains(&b));
         }
+    }
+}
 
-        // TODO: Eventually, instead of looping over all the entities, will use some sort of
-        // quadtree. Doing so would allow parallelizing this part.
-        if self.entities.len() > 1 {
-            for i in 0..self.entities.len() - 1 {
-                let Some(si) = self.entities[i].get_index_for(Component::Shape) else {
-                    continue;
-                };
-                let Some(pi) = self.entities[i].get_index_for(Component::Physics) else {
-                    continue;
-                };
-
-                for j in i + 1..self.entities.len() {
-                    let Some(sj) = self.entities[j].get_index_for(Component::Shape) else {
-                        break;
-                    };
-                    let Some(pj) = self.entities[j].get_index_for(Component::Physics) else {
-                        break;
-                    };
-
-                    if let Some(collision) = Collision::detect(
-                        &self.shape_components[si],
-                        &self.shape_components[sj],
-                        &self.physics_components[pi],
-                        &self.physics_components[pj],
-                    ) {
-                        self.entities[j].colliding = true;
-                        self.entities[i].colliding = true;
-
-                        let displacement = collision.resolve_penetration(
-                            self.physics_components[pi].inverse_mass,
-                            self.physics_components[pj].inverse_mass,
-                        );
-
-                        self.physics_components[pi].position -= displacement.0;
-                        self.physics_components[pj].position += displacement.1;
-
-                        let impulse = collision.resolve_impulse(
-                            &self.physics_components[pi],
-                            &self.physics_components[pj],
-                        );
-
-                        self.physics_components[pi].apply_impulse(impulse);
-                        self.physics_components[pj].apply_impulse(-impulse);
-                    }
-                }
-            }
-        }
-        // TODO: This is a very hacky way to keep objects on screen.
-        self.physics_components.iter_mut().for_each(|physics| {
-            if physics.position.x <= self.upper_left.x {
-                physics.position.x = self.upper_left.x;
-                physics.velocity.x = 0.0;
-            } else if physics.position.x >= self.lower_right.x {
-                physics.position.x = self.lower_right.x;
-                physics.velocity.x = 0.0;
-            }
-
-            if physics.position.y <= self.upper_left.y {
-                physics.position.y = self.upper_left.y;
-                physics.velocity.y = 0.0;
-            } else if physics.position.y >= self.lower_right.y {
-                physics.position.y = self.lower_right.y;
-                physics.velocity.y = 0.0;
-            }
-        });
+// Static walls just outside the rectangle from ul to lr, so everything stays inside.
+// Restitution 1.0, the bodies hitting them decide how much they bounce.
+fn add_boundaries(physics: &mut catphys::World, ul: Vec2, lr: Vec2) {
+    let thickness = 1.0 * PIXELS_PER_METER;
+    let (w, h) = (lr.x - ul.x, lr.y - ul.y);
+    let center = (ul + lr) * 0.5;
+
+    let walls = [
+        (
+            Vec2::new(center.x, ul.y - thickness * 0.5),
+            w + thickness * 2.0,
+            thickness,
+        ),
+        (
+            Vec2::new(center.x, lr.y + thickness * 0.5),
+            w + thickness * 2.0,
+            thickness,
+        ),
+        (Vec2::new(ul.x - thickness * 0.5, center.y), thickness, h),
+        (Vec2::new(lr.x + thickness * 0.5, center.y), thickness, h),
+    ];
+    for (position, w, h) in walls {
+        physics.add_body(Physics::new_static(position, 1.0), Shape::Rect { w, h });
     }
 }